@@ -0,0 +1,146 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse_quote, Data, DeriveInput, Error, Fields};
+
+pub fn expand(input: DeriveInput) -> Result<TokenStream, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) if !fields.named.is_empty() => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "Invocation can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "Invocation can only be derived for structs",
+            ))
+        }
+    };
+
+    let vis = &input.vis;
+    let name = &input.ident;
+    let state = format_ident!("{}State", name);
+    let output = format_ident!("{}Output", name);
+    let names: Vec<_> = fields.iter().map(|field| &field.ident).collect();
+    // Locals of `may_fail_detailed` must not clash with the field names.
+    let states: Vec<_> = (0..names.len())
+        .map(|n| format_ident!("__state_{}", n))
+        .collect();
+    let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+    let first = types[0];
+    let error = quote!(<#first as ::strong_function::Invocation>::Error);
+
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for ty in &types {
+        where_clause
+            .predicates
+            .push(parse_quote!(#ty: ::strong_function::Invocation));
+    }
+    for ty in &types[1..] {
+        where_clause.predicates.push(parse_quote!(
+            #error: ::core::convert::From<<#ty as ::strong_function::Invocation>::Error>
+        ));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // If preparing field `n` fails or panics, fields `0..n` must be aborted.
+    let prepare_fields = names.iter().zip(&states).enumerate().map(|(n, (name, state))| {
+        let prepared = &names[..n];
+        let prepared_states = &states[..n];
+        quote! {
+            let __result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+                ::strong_function::Invocation::may_fail_detailed(&self.#name)
+            }));
+            let #state = match __result {
+                ::core::result::Result::Ok(::core::result::Result::Ok(__tmp)) => __tmp,
+                ::core::result::Result::Err(__panic) => {
+                    // The panic is reported instead of errors raised while aborting.
+                    #(
+                        let _ =
+                            ::strong_function::Invocation::abort(&self.#prepared, #prepared_states);
+                    )*
+                    ::std::panic::resume_unwind(__panic)
+                }
+                ::core::result::Result::Ok(::core::result::Result::Err(__failure)) => {
+                    let mut __failure: ::strong_function::Failure<Self::Error> =
+                        __failure.at(#n).map(::core::convert::From::from);
+                    #(
+                        if let ::core::result::Result::Err(__error) =
+                            ::strong_function::Invocation::abort(&self.#prepared, #prepared_states)
+                        {
+                            __failure.abort_errors.push(::core::convert::From::from(__error));
+                        }
+                    )*
+                    return ::core::result::Result::Err(__failure);
+                }
+            };
+        }
+    });
+
+    let state_doc = format!("Intermediate state of [`{name}`], one member per field.");
+    let output_doc = format!("Output of [`{name}`], one member per field.");
+
+    Ok(quote! {
+        #[doc = #state_doc]
+        #vis struct #state #impl_generics #where_clause {
+            #(#vis #names: <#types as ::strong_function::Invocation>::IntermediateState,)*
+        }
+
+        #[doc = #output_doc]
+        #vis struct #output #impl_generics #where_clause {
+            #(#vis #names: <#types as ::strong_function::Invocation>::Output,)*
+        }
+
+        impl #impl_generics ::strong_function::Invocation for #name #ty_generics #where_clause {
+            type Error = #error;
+            type Output = #output #ty_generics;
+            type IntermediateState = #state #ty_generics;
+
+            fn may_fail(&self) -> ::core::result::Result<Self::IntermediateState, Self::Error> {
+                ::strong_function::Invocation::may_fail_detailed(self)
+                    .map_err(|failure| failure.cause)
+            }
+
+            fn commit(self, tmp: Self::IntermediateState) -> Self::Output {
+                #output {
+                    #(#names: ::strong_function::Invocation::commit(self.#names, tmp.#names),)*
+                }
+            }
+
+            fn abort(
+                &self,
+                tmp: Self::IntermediateState,
+            ) -> ::core::result::Result<(), Self::Error> {
+                // Abort every field, even if aborting an earlier one fails.
+                let mut result = ::core::result::Result::Ok(());
+                #(
+                    if let ::core::result::Result::Err(error) =
+                        ::strong_function::Invocation::abort(&self.#names, tmp.#names)
+                    {
+                        if result.is_ok() {
+                            result = ::core::result::Result::Err(
+                                ::core::convert::From::from(error),
+                            );
+                        }
+                    }
+                )*
+                result
+            }
+
+            fn may_fail_detailed(
+                &self,
+            ) -> ::core::result::Result<
+                Self::IntermediateState,
+                ::strong_function::Failure<Self::Error>,
+            > {
+                #(#prepare_fields)*
+                ::core::result::Result::Ok(#state { #(#names: #states),* })
+            }
+        }
+    })
+}
@@ -0,0 +1,67 @@
+//! Procedural macros for `strong-function`. Use them via the `macros` feature of
+//! `strong-function`, rather than depending on this crate directly.
+
+mod derive_invocation;
+mod strong_function;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput, ItemFn};
+
+/// Implements `Invocation` for a struct whose fields are all invocations. `may_fail` of every field
+/// is executed before any field is committed. If a field fails, the fields prepared before it are
+/// aborted.
+///
+/// For a struct `Batch` two structs `BatchState` and `BatchOutput` are generated. They hold the
+/// intermediate states and outputs of the fields under the same names. The error type is the one of
+/// the first field. Errors of all other fields must be convertible into it.
+#[proc_macro_derive(Invocation)]
+pub fn derive_invocation(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive_invocation::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Splits a function into the `may_fail` and `commit` phase of an invocation. The function must
+/// return `Result<Output, Error>` and contain exactly one `commit!` marker statement.
+///
+/// Statements before the marker form `may_fail`. They can see the arguments only by shared
+/// reference, may fail using `?` and must not change application state. The marker lists the local
+/// variables passed on to the commit phase together with their types, e.g.
+/// `commit!(new_balance: u64)`. Statements after the marker form `commit`. They may change
+/// application state, must end with `Ok(output)` and must not contain `?` or `return`.
+///
+/// The function is replaced by a constructor with the same name and arguments, returning a struct
+/// named after the function in upper camel case, which implements `Invocation`.
+///
+/// ```
+/// # use strong_function::{strong_function, Invocation};
+/// #[strong_function]
+/// fn withdraw(balance: &mut u64, amount: u64) -> Result<u64, String> {
+///     let new_balance = balance
+///         .checked_sub(*amount)
+///         .ok_or_else(|| format!("Can not withdraw {amount} from {balance}"))?;
+///     commit!(new_balance: u64);
+///     *balance = new_balance;
+///     Ok(new_balance)
+/// }
+///
+/// let mut balance = 10;
+/// withdraw(&mut balance, 3).execute().unwrap();
+/// assert_eq!(7, balance);
+/// ```
+#[proc_macro_attribute]
+pub fn strong_function(attribute: TokenStream, item: TokenStream) -> TokenStream {
+    if !attribute.is_empty() {
+        return syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "#[strong_function] does not take any arguments",
+        )
+        .into_compile_error()
+        .into();
+    }
+    let function = parse_macro_input!(item as ItemFn);
+    strong_function::expand(function)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,336 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    parse::Parser,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    visit::Visit,
+    visit_mut::{self, VisitMut},
+    Error, Expr, ExprAsync, ExprClosure, ExprReturn, ExprTry, FnArg, GenericArgument, Ident, Item,
+    ItemFn, Lifetime, LifetimeParam, Pat, PathArguments, ReturnType, Stmt, Token, Type,
+    TypeReference,
+};
+
+pub fn expand(function: ItemFn) -> Result<TokenStream, Error> {
+    let signature = &function.sig;
+    if let Some(asyncness) = &signature.asyncness {
+        return Err(Error::new_spanned(
+            asyncness,
+            "#[strong_function] does not support async functions",
+        ));
+    }
+    let (output, error) = result_types(&signature.output)?;
+
+    // Elided lifetimes are fine in a function signature, but not in the members of a struct.
+    let mut namer = NameElidedLifetimes {
+        generated: Vec::new(),
+    };
+    let mut names = Vec::new();
+    let mut mutability = Vec::new();
+    let mut types = Vec::new();
+    for input in &signature.inputs {
+        let FnArg::Typed(argument) = input else {
+            return Err(Error::new_spanned(
+                input,
+                "#[strong_function] does not support methods",
+            ));
+        };
+        let Pat::Ident(pattern) = &*argument.pat else {
+            return Err(Error::new_spanned(
+                &argument.pat,
+                "#[strong_function] only supports identifiers as argument patterns",
+            ));
+        };
+        let mut ty = (*argument.ty).clone();
+        namer.visit_type_mut(&mut ty);
+        names.push(&pattern.ident);
+        mutability.push(&pattern.mutability);
+        types.push(ty);
+    }
+    let mut generics = signature.generics.clone();
+    // Lifetimes must be declared before any other generic parameter.
+    let position = generics.lifetimes().count();
+    for (offset, lifetime) in namer.generated.into_iter().enumerate() {
+        generics
+            .params
+            .insert(position + offset, LifetimeParam::new(lifetime).into());
+    }
+
+    let (before, marker, after) = split_at_marker(&function.block.stmts, &function.block)?;
+    let state = Punctuated::<StateMember, Token![,]>::parse_terminated.parse2(marker)?;
+    let state_names: Vec<_> = state.iter().map(|member| &member.name).collect();
+    let state_types: Vec<_> = state.iter().map(|member| &member.ty).collect();
+    let (after, result) = commit_result(after)?;
+    for stmt in after {
+        reject_early_exit(stmt)?;
+    }
+    reject_early_exit_in_expr(result)?;
+
+    let attributes = &function.attrs;
+    let vis = &function.vis;
+    let name = &signature.ident;
+    let invocation = format_ident!(
+        "{}",
+        upper_camel_case(&name.to_string()),
+        span = name.span()
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let struct_doc = format!("Invocation of [`{name}`].");
+    // Arguments are only available by shared reference, while executing `may_fail`.
+    let shared: Vec<_> = types
+        .iter()
+        .map(|ty| match ty {
+            Type::Reference(TypeReference { elem, .. }) => quote!(&#elem),
+            ty => quote!(&#ty),
+        })
+        .collect();
+    let borrow: Vec<_> = types
+        .iter()
+        .zip(&names)
+        .map(|(ty, name)| match ty {
+            Type::Reference(_) => quote!(&*self.#name),
+            _ => quote!(&self.#name),
+        })
+        .collect();
+    // Arguments become locals of `commit`, so the state must not be nameable by user code.
+    let tmp = Ident::new("tmp", Span::mixed_site());
+
+    Ok(quote! {
+        #[doc = #struct_doc]
+        #vis struct #invocation #impl_generics #where_clause {
+            #(#names: #types,)*
+        }
+
+        #(#attributes)*
+        #vis fn #name #impl_generics (#(#names: #types),*) -> #invocation #ty_generics
+        #where_clause
+        {
+            #invocation { #(#names),* }
+        }
+
+        impl #impl_generics ::strong_function::Invocation for #invocation #ty_generics
+        #where_clause
+        {
+            type Error = #error;
+            type Output = #output;
+            type IntermediateState = (#(#state_types,)*);
+
+            #[allow(unused_variables)]
+            fn may_fail(&self) -> ::core::result::Result<Self::IntermediateState, Self::Error> {
+                #(let #names: #shared = #borrow;)*
+                #(#before)*
+                ::core::result::Result::Ok((#(#state_names,)*))
+            }
+
+            #[allow(unused_variables, unused_mut)]
+            fn commit(self, #tmp: Self::IntermediateState) -> Self::Output {
+                let #invocation { #(#mutability #names),* } = self;
+                let (#(#state_names,)*) = #tmp;
+                #(#after)*
+                #result
+            }
+        }
+    })
+}
+
+/// Member of the intermediate state, as listed in the `commit!` marker, e.g. `new_balance: u64`.
+struct StateMember {
+    name: Ident,
+    ty: Type,
+}
+
+impl syn::parse::Parse for StateMember {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let ty = input.parse()?;
+        Ok(StateMember { name, ty })
+    }
+}
+
+/// Extracts `O` and `E` from a return type `Result<O, E>`.
+fn result_types(output: &ReturnType) -> Result<(&Type, &Type), Error> {
+    let message = "#[strong_function] requires the return type to be `Result<Output, Error>`";
+    let ReturnType::Type(_, ty) = output else {
+        return Err(Error::new_spanned(output, message));
+    };
+    let Type::Path(path) = &**ty else {
+        return Err(Error::new_spanned(ty, message));
+    };
+    let last = path.path.segments.last().expect("Paths are never empty");
+    match &last.arguments {
+        PathArguments::AngleBracketed(arguments)
+            if last.ident == "Result" && arguments.args.len() == 2 =>
+        {
+            match (&arguments.args[0], &arguments.args[1]) {
+                (GenericArgument::Type(output), GenericArgument::Type(error)) => {
+                    Ok((output, error))
+                }
+                _ => Err(Error::new_spanned(ty, message)),
+            }
+        }
+        _ => Err(Error::new_spanned(ty, message)),
+    }
+}
+
+/// Splits the statements of the function body at the `commit!` marker. Returns the statements
+/// before the marker, the tokens within the marker and the statements after it.
+fn split_at_marker<'a>(
+    stmts: &'a [Stmt],
+    block: &syn::Block,
+) -> Result<(&'a [Stmt], TokenStream, &'a [Stmt]), Error> {
+    let mut markers = stmts
+        .iter()
+        .enumerate()
+        .filter_map(|(index, stmt)| match stmt {
+            Stmt::Macro(stmt) if stmt.mac.path.is_ident("commit") => Some((index, stmt)),
+            _ => None,
+        });
+    let Some((index, marker)) = markers.next() else {
+        return Err(Error::new_spanned(
+            block,
+            "#[strong_function] requires a `commit!` marker in the function body",
+        ));
+    };
+    if let Some((_, second)) = markers.next() {
+        return Err(Error::new_spanned(
+            second,
+            "Only one `commit!` marker is allowed",
+        ));
+    }
+    Ok((
+        &stmts[..index],
+        marker.mac.tokens.clone(),
+        &stmts[index + 1..],
+    ))
+}
+
+/// The statements after the marker must end in `Ok(output)`. Returns the statements before the
+/// final expression and `output`.
+fn commit_result(stmts: &[Stmt]) -> Result<(&[Stmt], &Expr), Error> {
+    let message = "The code after `commit!` must end with `Ok(output)`";
+    let Some((Stmt::Expr(last, None), stmts)) = stmts.split_last() else {
+        return Err(Error::new(
+            stmts.last().map_or_else(Span::call_site, Spanned::span),
+            message,
+        ));
+    };
+    match last {
+        Expr::Call(call)
+            if call.args.len() == 1
+                && matches!(&*call.func, Expr::Path(path) if path.path.is_ident("Ok")) =>
+        {
+            Ok((stmts, &call.args[0]))
+        }
+        _ => Err(Error::new_spanned(last, message)),
+    }
+}
+
+fn reject_early_exit(stmt: &Stmt) -> Result<(), Error> {
+    let mut visitor = EarlyExit { error: None };
+    visitor.visit_stmt(stmt);
+    visitor.error.map_or(Ok(()), Err)
+}
+
+fn reject_early_exit_in_expr(expr: &Expr) -> Result<(), Error> {
+    let mut visitor = EarlyExit { error: None };
+    visitor.visit_expr(expr);
+    visitor.error.map_or(Ok(()), Err)
+}
+
+/// Finds `?` and `return` in the commit phase. Closures, async blocks and nested items have their
+/// own control flow, so they are skipped.
+struct EarlyExit {
+    error: Option<Error>,
+}
+
+impl<'ast> Visit<'ast> for EarlyExit {
+    fn visit_expr_try(&mut self, node: &'ast ExprTry) {
+        self.error.get_or_insert_with(|| {
+            Error::new_spanned(
+                node,
+                "`?` is not allowed after `commit!`, committing must not fail",
+            )
+        });
+    }
+
+    fn visit_expr_return(&mut self, node: &'ast ExprReturn) {
+        self.error.get_or_insert_with(|| {
+            Error::new_spanned(node, "`return` is not allowed after `commit!`")
+        });
+    }
+
+    fn visit_expr_closure(&mut self, _: &'ast ExprClosure) {}
+
+    fn visit_expr_async(&mut self, _: &'ast ExprAsync) {}
+
+    fn visit_item(&mut self, _: &'ast Item) {}
+}
+
+/// Replaces elided lifetimes in references and `'_` with named ones.
+struct NameElidedLifetimes {
+    generated: Vec<Lifetime>,
+}
+
+impl NameElidedLifetimes {
+    fn fresh(&mut self, span: Span) -> Lifetime {
+        let name = format!("'__strong_function_{}", self.generated.len());
+        let lifetime = Lifetime::new(&name, span);
+        self.generated.push(lifetime.clone());
+        lifetime
+    }
+}
+
+impl VisitMut for NameElidedLifetimes {
+    fn visit_type_reference_mut(&mut self, node: &mut TypeReference) {
+        if node.lifetime.is_none() {
+            node.lifetime = Some(self.fresh(node.and_token.span));
+        }
+        visit_mut::visit_type_reference_mut(self, node)
+    }
+
+    fn visit_lifetime_mut(&mut self, node: &mut Lifetime) {
+        if node.ident == "_" {
+            *node = self.fresh(node.span());
+        }
+    }
+}
+
+fn upper_camel_case(snake_case: &str) -> String {
+    snake_case
+        .split('_')
+        .flat_map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars))
+                .into_iter()
+                .flatten()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::parse_quote;
+
+    use super::expand;
+
+    #[test]
+    fn question_mark_after_commit_is_rejected() {
+        let function = parse_quote! {
+            fn set(target: &mut i32, value: &str) -> Result<(), std::num::ParseIntError> {
+                commit!();
+                *target = value.parse()?;
+                Ok(())
+            }
+        };
+
+        let error = expand(function).unwrap_err();
+
+        assert_eq!(
+            "`?` is not allowed after `commit!`, committing must not fail",
+            error.to_string()
+        )
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    cell::Cell,
+    panic::{self, AssertUnwindSafe},
+};
+
+use strong_function::{from_fns, ChainError, Invocation};
+
+/// Sets a cell to a value, but only if the cell does not hold a larger value already.
+struct Increase<'a> {
+    cell: &'a Cell<i32>,
+    value: i32,
+}
+
+impl<'a> Invocation for Increase<'a> {
+    type Error = String;
+    type Output = i32;
+    type IntermediateState = ();
+
+    fn may_fail(&self) -> Result<(), String> {
+        if self.cell.get() > self.value {
+            Err(format!(
+                "{} is smaller than {}",
+                self.value,
+                self.cell.get()
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn commit(self, _: ()) -> i32 {
+        self.cell.replace(self.value)
+    }
+}
+
+/// Counts how often it has been aborted.
+struct CountAborts<'a> {
+    aborts: &'a Cell<u32>,
+}
+
+impl<'a> Invocation for CountAborts<'a> {
+    type Error = String;
+    type Output = ();
+    type IntermediateState = ();
+
+    fn may_fail(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn commit(self, _: ()) {}
+
+    fn abort(&self, _: ()) -> Result<(), String> {
+        self.aborts.set(self.aborts.get() + 1);
+        Err("abort failed".to_owned())
+    }
+}
+
+#[derive(Invocation)]
+struct Batch<'a> {
+    first: Increase<'a>,
+    second: Increase<'a>,
+}
+
+#[test]
+fn named_outputs() {
+    let a = Cell::new(1);
+    let b = Cell::new(2);
+    let batch = Batch {
+        first: Increase { cell: &a, value: 3 },
+        second: Increase { cell: &b, value: 4 },
+    };
+
+    let output = batch.execute().unwrap();
+
+    assert_eq!(1, output.first);
+    assert_eq!(2, output.second);
+    assert_eq!((3, 4), (a.get(), b.get()));
+}
+
+#[test]
+fn no_field_is_committed_if_one_fails() {
+    let a = Cell::new(1);
+    let b = Cell::new(5);
+    let batch = Batch {
+        first: Increase { cell: &a, value: 3 },
+        second: Increase { cell: &b, value: 4 },
+    };
+
+    let result = batch.execute().map(|_| ());
+
+    assert_eq!(Err("4 is smaller than 5".to_owned()), result);
+    assert_eq!((1, 5), (a.get(), b.get()));
+}
+
+#[derive(Invocation)]
+struct AbortFirst<'a> {
+    first: CountAborts<'a>,
+    second: Increase<'a>,
+}
+
+#[test]
+fn prepared_fields_are_aborted() {
+    let aborts = Cell::new(0);
+    let b = Cell::new(5);
+    let batch = AbortFirst {
+        first: CountAborts { aborts: &aborts },
+        second: Increase { cell: &b, value: 4 },
+    };
+
+    let failure = batch.traced().execute_detailed().map(|_| ()).unwrap_err();
+
+    assert_eq!("4 is smaller than 5", failure.cause.source);
+    assert_eq!(vec![1], failure.cause.path);
+    assert_eq!(
+        vec![ChainError::new("abort failed".to_owned())],
+        failure.abort_errors
+    );
+    assert_eq!(1, aborts.get());
+}
+
+#[derive(Invocation)]
+struct Generic<I, J> {
+    first: I,
+    second: J,
+}
+
+#[test]
+fn generic_fields() {
+    let batch = Generic {
+        first: from_fns(|| Ok::<_, ()>(1), |n| n + 1),
+        second: from_fns(|| Ok("state"), |s: &str| s.len()),
+    };
+
+    let GenericOutput { first, second } = batch.execute().unwrap();
+
+    assert_eq!((2, 5), (first, second))
+}
+
+/// Field names which used to clash with locals of the generated code.
+#[derive(Invocation)]
+struct Names<'a> {
+    failure: CountAborts<'a>,
+    __tmp: Increase<'a>,
+}
+
+#[test]
+fn field_names_do_not_clash_with_generated_code() {
+    let aborts = Cell::new(0);
+    let b = Cell::new(5);
+    let batch = Names {
+        failure: CountAborts { aborts: &aborts },
+        __tmp: Increase { cell: &b, value: 4 },
+    };
+
+    let failure = batch.execute_detailed().map(|_| ()).unwrap_err();
+
+    assert_eq!("4 is smaller than 5", failure.cause);
+    assert_eq!(1, aborts.get());
+}
+
+#[derive(Invocation)]
+struct AbortOnPanic<'a, I> {
+    first: CountAborts<'a>,
+    second: I,
+}
+
+#[test]
+fn prepared_fields_are_aborted_if_one_panics() {
+    let aborts = Cell::new(0);
+    let batch = AbortOnPanic {
+        first: CountAborts { aborts: &aborts },
+        second: from_fns(
+            || -> Result<(), String> { panic!("bug in validation") },
+            |()| (),
+        ),
+    };
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| batch.execute()));
+
+    assert!(result.is_err());
+    assert_eq!(1, aborts.get());
+}
@@ -0,0 +1,73 @@
+use strong_function::{strong_function, Invocation};
+
+struct Account {
+    balance: u64,
+}
+
+#[strong_function]
+fn withdraw(account: &mut Account, amount: u64) -> Result<u64, String> {
+    let new_balance = account
+        .balance
+        .checked_sub(*amount)
+        .ok_or_else(|| format!("Insufficient funds to withdraw {amount}"))?;
+    commit!(new_balance: u64);
+    account.balance = new_balance;
+    Ok(new_balance)
+}
+
+#[test]
+fn split_function() {
+    let mut account = Account { balance: 10 };
+
+    let new_balance = withdraw(&mut account, 3).execute();
+
+    assert_eq!(Ok(7), new_balance);
+    assert_eq!(7, account.balance);
+}
+
+#[test]
+fn chain_split_functions() {
+    let mut first = Account { balance: 10 };
+    let mut second = Account { balance: 2 };
+
+    let result = (withdraw(&mut first, 3), withdraw(&mut second, 3)).execute();
+
+    assert_eq!(Err("Insufficient funds to withdraw 3".to_owned()), result);
+    assert_eq!((10, 2), (first.balance, second.balance));
+}
+
+#[strong_function]
+fn push_all<T: Clone>(target: &mut Vec<T>, items: &[T]) -> Result<usize, ()> {
+    commit!();
+    target.extend_from_slice(items);
+    Ok(target.len())
+}
+
+#[test]
+fn generic_function_without_state() {
+    let mut target = vec![1];
+
+    let len = push_all(&mut target, &[2, 3]).execute();
+
+    assert_eq!(Ok(3), len);
+    assert_eq!(vec![1, 2, 3], target);
+}
+
+/// Argument name which used to shadow the state within the generated `commit`.
+#[strong_function]
+fn set(target: &mut u64, tmp: u64) -> Result<u64, String> {
+    let value = tmp + 1;
+    commit!(value: u64);
+    *target = value;
+    Ok(tmp)
+}
+
+#[test]
+fn argument_names_do_not_clash_with_generated_code() {
+    let mut target = 0;
+
+    let output = set(&mut target, 41).execute();
+
+    assert_eq!(Ok(41), output);
+    assert_eq!(42, target);
+}
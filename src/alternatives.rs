@@ -0,0 +1,180 @@
+use crate::{Either, Failure, Invocation};
+
+/// Tries a second invocation, if `may_fail` of the first one fails. Only the alternative which
+/// succeeded is committed. Created by [`Invocation::or_else`].
+pub struct OrElse<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> OrElse<A, B> {
+    pub(crate) fn new(first: A, second: B) -> Self {
+        OrElse { first, second }
+    }
+}
+
+impl<A, B, E> Invocation for OrElse<A, B>
+where
+    A: Invocation<Error = E>,
+    B: Invocation<Error = E>,
+{
+    /// Errors of both alternatives, if both fail.
+    type Error = Vec<E>;
+    type Output = Either<A::Output, B::Output>;
+    type IntermediateState = Either<A::IntermediateState, B::IntermediateState>;
+
+    fn may_fail(&self) -> Result<Self::IntermediateState, Vec<E>> {
+        self.may_fail_detailed().map_err(|failure| failure.cause)
+    }
+
+    fn commit(self, tmp: Self::IntermediateState) -> Self::Output {
+        match tmp {
+            Either::Left(tmp) => Either::Left(self.first.commit(tmp)),
+            Either::Right(tmp) => Either::Right(self.second.commit(tmp)),
+        }
+    }
+
+    fn abort(&self, tmp: Self::IntermediateState) -> Result<(), Vec<E>> {
+        match tmp {
+            Either::Left(tmp) => self.first.abort(tmp),
+            Either::Right(tmp) => self.second.abort(tmp),
+        }
+        .map_err(|error| vec![error])
+    }
+
+    fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<Vec<E>>> {
+        let first = match self.first.may_fail_detailed() {
+            Ok(tmp) => return Ok(Either::Left(tmp)),
+            Err(failure) => failure,
+        };
+        match self.second.may_fail_detailed() {
+            Ok(tmp) => Ok(Either::Right(tmp)),
+            Err(second) => Err(collect_failures([first, second])),
+        }
+    }
+}
+
+/// Invocation trying alternatives in order, committing only the first whose `may_fail` succeeds.
+/// If all of them fail, the errors of every alternative are reported.
+///
+/// `first_ok` of no alternatives always fails, with an empty list of errors.
+pub fn first_ok<I>(alternatives: impl IntoIterator<Item = I>) -> FirstOk<I>
+where
+    I: Invocation,
+{
+    FirstOk {
+        alternatives: alternatives.into_iter().collect(),
+    }
+}
+
+/// Commits only the first alternative which could be prepared. See [`first_ok`].
+pub struct FirstOk<I> {
+    alternatives: Vec<I>,
+}
+
+impl<I> Invocation for FirstOk<I>
+where
+    I: Invocation,
+{
+    /// Errors of every alternative, if all of them fail.
+    type Error = Vec<I::Error>;
+    type Output = I::Output;
+    /// Position of the alternative which succeeded and its intermediate state.
+    type IntermediateState = (usize, I::IntermediateState);
+
+    fn may_fail(&self) -> Result<Self::IntermediateState, Self::Error> {
+        self.may_fail_detailed().map_err(|failure| failure.cause)
+    }
+
+    fn commit(self, (index, tmp): Self::IntermediateState) -> Self::Output {
+        self.alternatives
+            .into_iter()
+            .nth(index)
+            .expect("Intermediate state must stem from the same invocation")
+            .commit(tmp)
+    }
+
+    fn abort(&self, (index, tmp): Self::IntermediateState) -> Result<(), Self::Error> {
+        self.alternatives[index]
+            .abort(tmp)
+            .map_err(|error| vec![error])
+    }
+
+    fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<Self::Error>> {
+        let mut failures = Vec::new();
+        for (index, alternative) in self.alternatives.iter().enumerate() {
+            match alternative.may_fail_detailed() {
+                Ok(tmp) => return Ok((index, tmp)),
+                Err(failure) => failures.push(failure),
+            }
+        }
+        Err(collect_failures(failures))
+    }
+}
+
+/// Combines the failures of all alternatives into one.
+fn collect_failures<E>(failures: impl IntoIterator<Item = Failure<E>>) -> Failure<Vec<E>> {
+    let mut causes = Vec::new();
+    let mut abort_errors = Vec::new();
+    for failure in failures {
+        causes.push(failure.cause);
+        abort_errors.extend(failure.abort_errors.into_iter().map(|error| vec![error]));
+    }
+    let mut failure = Failure::new(causes);
+    failure.abort_errors = abort_errors;
+    failure
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::{from_fns, tests::RejectOdd, Either, Invocation};
+
+    use super::first_ok;
+
+    /// Stores `5` in `target`, if it is available.
+    fn store(
+        target: &Cell<i32>,
+        available: bool,
+    ) -> impl Invocation<Error = &'static str, Output = ()> + '_ {
+        from_fns(
+            move || if available { Ok(5) } else { Err("unavailable") },
+            |n| target.set(n),
+        )
+    }
+
+    #[test]
+    fn fall_back_to_second_alternative() {
+        let primary = Cell::new(0);
+        let fallback = Cell::new(0);
+
+        let output = store(&primary, false)
+            .or_else(store(&fallback, true))
+            .execute();
+
+        assert_eq!(Ok(Either::Right(())), output);
+        assert_eq!((0, 5), (primary.get(), fallback.get()));
+    }
+
+    #[test]
+    fn or_else_reports_both_errors() {
+        let output = RejectOdd(1).or_else(RejectOdd(3)).execute();
+
+        assert_eq!(Err(vec![1, 3]), output)
+    }
+
+    #[test]
+    fn first_ok_commits_first_success_only() {
+        let output = first_ok([RejectOdd(1), RejectOdd(2), RejectOdd(4)]).execute();
+
+        assert_eq!(Ok(2), output)
+    }
+
+    #[test]
+    fn first_ok_collects_every_error() {
+        let output = first_ok([RejectOdd(1), RejectOdd(3)]).execute();
+
+        assert_eq!(Err(vec![1, 3]), output)
+    }
+}
@@ -0,0 +1,588 @@
+use std::{
+    future::{poll_fn, ready, Future},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    failure::{abort_all, ok_or_record, unwrap_prepared},
+    for_each_tuple, Failure, Invocation,
+};
+
+/// Like [`Invocation`], but `may_fail` is asynchronous. Useful if validation involves I/O, like
+/// reading files or asking other services. `commit` stays synchronous and infallible.
+///
+/// Tuples and `Vec`s of async invocations execute `may_fail` of their elements concurrently. Use
+/// [`Lift`] to take part with a synchronous invocation.
+///
+/// The futures returned by this trait are not required to be `Send`. For a concrete type they are
+/// `Send` if their contents are, but generic code can not rely on it. Use
+/// [`SendAsyncInvocation`] to spawn the execution of generic invocations on a multithreaded
+/// executor.
+pub trait AsyncInvocation: Sized {
+    type Error;
+    type Output;
+    type IntermediateState;
+
+    fn may_fail(&self) -> impl Future<Output = Result<Self::IntermediateState, Self::Error>>;
+
+    fn commit(self, tmp: Self::IntermediateState) -> Self::Output;
+
+    /// See [`Invocation::abort`].
+    fn abort(&self, tmp: Self::IntermediateState) -> Result<(), Self::Error> {
+        drop(tmp);
+        Ok(())
+    }
+
+    /// See [`Invocation::may_fail_detailed`]. Async invocations can not be traced, so
+    /// [`Failure::path`] is always empty.
+    fn may_fail_detailed(
+        &self,
+    ) -> impl Future<Output = Result<Self::IntermediateState, Failure<Self::Error>>> {
+        async move { self.may_fail().await.map_err(Failure::new) }
+    }
+
+    fn execute(self) -> impl Future<Output = Result<Self::Output, Self::Error>> {
+        async move {
+            let tmp = self.may_fail().await?;
+            Ok(self.commit(tmp))
+        }
+    }
+
+    /// See [`Invocation::execute_detailed`].
+    fn execute_detailed(self) -> impl Future<Output = Result<Self::Output, Failure<Self::Error>>> {
+        async move {
+            let tmp = self.may_fail_detailed().await?;
+            Ok(self.commit(tmp))
+        }
+    }
+}
+
+/// An [`AsyncInvocation`] whose futures are `Send`, so generic code can spawn them on a
+/// multithreaded executor. For a type whose `may_fail` future is `Send`, implement `may_fail_send`
+/// by forwarding to `may_fail`.
+///
+/// Implemented for [`Lift`], as well as tuples and `Vec`s of `SendAsyncInvocation`s.
+pub trait SendAsyncInvocation:
+    AsyncInvocation<Error: Send, Output: Send, IntermediateState: Send> + Send + Sync
+{
+    /// Same as [`AsyncInvocation::may_fail`].
+    fn may_fail_send(
+        &self,
+    ) -> impl Future<Output = Result<Self::IntermediateState, Self::Error>> + Send;
+
+    /// Same as [`AsyncInvocation::may_fail_detailed`].
+    fn may_fail_detailed_send(
+        &self,
+    ) -> impl Future<Output = Result<Self::IntermediateState, Failure<Self::Error>>> + Send {
+        async move { self.may_fail_send().await.map_err(Failure::new) }
+    }
+
+    /// Same as [`AsyncInvocation::execute`].
+    fn execute_send(self) -> impl Future<Output = Result<Self::Output, Self::Error>> + Send {
+        async move {
+            let tmp = self.may_fail_send().await?;
+            Ok(self.commit(tmp))
+        }
+    }
+
+    /// Same as [`AsyncInvocation::execute_detailed`].
+    fn execute_detailed_send(
+        self,
+    ) -> impl Future<Output = Result<Self::Output, Failure<Self::Error>>> + Send {
+        async move {
+            let tmp = self.may_fail_detailed_send().await?;
+            Ok(self.commit(tmp))
+        }
+    }
+}
+
+/// Adapts a synchronous invocation, so it can be used as an [`AsyncInvocation`].
+pub struct Lift<I>(I);
+
+impl<I> Lift<I> {
+    pub fn new(invocation: I) -> Self {
+        Lift(invocation)
+    }
+}
+
+impl<I> AsyncInvocation for Lift<I>
+where
+    I: Invocation,
+{
+    type Error = I::Error;
+    type Output = I::Output;
+    type IntermediateState = I::IntermediateState;
+
+    fn may_fail(&self) -> impl Future<Output = Result<Self::IntermediateState, Self::Error>> {
+        ready(self.0.may_fail())
+    }
+
+    fn commit(self, tmp: Self::IntermediateState) -> Self::Output {
+        self.0.commit(tmp)
+    }
+
+    fn abort(&self, tmp: Self::IntermediateState) -> Result<(), Self::Error> {
+        self.0.abort(tmp)
+    }
+
+    fn may_fail_detailed(
+        &self,
+    ) -> impl Future<Output = Result<Self::IntermediateState, Failure<Self::Error>>> {
+        ready(self.0.may_fail_detailed())
+    }
+}
+
+impl<I> SendAsyncInvocation for Lift<I>
+where
+    I: Invocation<Error: Send, Output: Send, IntermediateState: Send> + Send + Sync,
+{
+    fn may_fail_send(
+        &self,
+    ) -> impl Future<Output = Result<Self::IntermediateState, Self::Error>> + Send {
+        ready(self.0.may_fail())
+    }
+
+    fn may_fail_detailed_send(
+        &self,
+    ) -> impl Future<Output = Result<Self::IntermediateState, Failure<Self::Error>>> + Send {
+        ready(self.0.may_fail_detailed())
+    }
+}
+
+/// Executes `$method` of every element of the tuple `$tuple` concurrently. If one of them fails,
+/// the prepared elements are aborted.
+macro_rules! prepare_tuple {
+    ($tuple:ident, $method:ident, $($i:tt),+) => {{
+        let mut futures = ($(Preparing::new(&$tuple.$i, $tuple.$i.$method()),)+);
+        poll_fn(|cx| {
+            let mut done = true;
+            $(done &= futures.$i.poll(cx);)+
+            if done {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        let mut failure = None;
+        let states = ($(ok_or_record(futures.$i.take(), &mut failure),)+);
+        match failure {
+            None => Ok(($(unwrap_prepared(states.$i),)+)),
+            Some(mut failure) => {
+                $(
+                    if let Some(tmp) = states.$i {
+                        failure.record_abort($tuple.$i.abort(tmp));
+                    }
+                )+
+                Err(failure)
+            }
+        }
+    }};
+}
+
+/// Implements [`AsyncInvocation`] and [`SendAsyncInvocation`] for a tuple of the given arity.
+macro_rules! impl_async_invocation_for_tuple {
+    ($($i:tt $F:ident),+) => {
+        /// Chains up to 16 async invocations. `may_fail` of all elements is executed concurrently.
+        /// If all of them succeed, the elements are committed in order. Otherwise the prepared
+        /// elements are aborted and the error of the first failing element is returned.
+        impl<E, $($F),+> AsyncInvocation for ($($F,)+)
+        where
+            $($F: AsyncInvocation<Error = E>,)+
+        {
+            type Error = E;
+            type Output = ($($F::Output,)+);
+            type IntermediateState = ($($F::IntermediateState,)+);
+
+            async fn may_fail(&self) -> Result<Self::IntermediateState, E> {
+                self.may_fail_detailed().await.map_err(|failure| failure.cause)
+            }
+
+            fn commit(self, tmp: Self::IntermediateState) -> Self::Output {
+                ($(self.$i.commit(tmp.$i),)+)
+            }
+
+            fn abort(&self, tmp: Self::IntermediateState) -> Result<(), E> {
+                abort_all([$(self.$i.abort(tmp.$i)),+])
+            }
+
+            async fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<E>> {
+                prepare_tuple!(self, may_fail_detailed, $($i),+)
+            }
+        }
+
+        impl<E, $($F),+> SendAsyncInvocation for ($($F,)+)
+        where
+            E: Send,
+            $($F: SendAsyncInvocation<Error = E>,)+
+        {
+            async fn may_fail_send(&self) -> Result<Self::IntermediateState, E> {
+                self.may_fail_detailed_send().await.map_err(|failure| failure.cause)
+            }
+
+            async fn may_fail_detailed_send(&self) -> Result<Self::IntermediateState, Failure<E>> {
+                prepare_tuple!(self, may_fail_detailed_send, $($i),+)
+            }
+        }
+    };
+}
+
+for_each_tuple!(impl_async_invocation_for_tuple);
+
+/// Executes `may_fail` of all elements concurrently. See the implementation for tuples.
+impl<I> AsyncInvocation for Vec<I>
+where
+    I: AsyncInvocation,
+{
+    type Error = I::Error;
+    type Output = Vec<I::Output>;
+    type IntermediateState = Vec<I::IntermediateState>;
+
+    async fn may_fail(&self) -> Result<Self::IntermediateState, Self::Error> {
+        self.may_fail_detailed()
+            .await
+            .map_err(|failure| failure.cause)
+    }
+
+    fn commit(self, tmp: Self::IntermediateState) -> Self::Output {
+        self.into_iter()
+            .zip(tmp)
+            .map(|(invocation, tmp)| invocation.commit(tmp))
+            .collect()
+    }
+
+    fn abort(&self, tmp: Self::IntermediateState) -> Result<(), Self::Error> {
+        abort_all(
+            self.iter()
+                .zip(tmp)
+                .map(|(invocation, tmp)| invocation.abort(tmp)),
+        )
+    }
+
+    async fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<Self::Error>> {
+        prepare_all(self, I::may_fail_detailed).await
+    }
+}
+
+impl<I> SendAsyncInvocation for Vec<I>
+where
+    I: SendAsyncInvocation,
+{
+    async fn may_fail_send(&self) -> Result<Self::IntermediateState, Self::Error> {
+        self.may_fail_detailed_send()
+            .await
+            .map_err(|failure| failure.cause)
+    }
+
+    fn may_fail_detailed_send(
+        &self,
+    ) -> impl Future<Output = Result<Self::IntermediateState, Failure<Self::Error>>> + Send {
+        prepare_all(self, I::may_fail_detailed_send)
+    }
+}
+
+/// Executes `prepare` for every element concurrently. If one of them fails, the prepared elements
+/// are aborted.
+async fn prepare_all<'a, I, F>(
+    invocations: &'a [I],
+    prepare: impl Fn(&'a I) -> F,
+) -> Result<Vec<I::IntermediateState>, Failure<I::Error>>
+where
+    I: AsyncInvocation,
+    F: Future<Output = Result<I::IntermediateState, Failure<I::Error>>>,
+{
+    let mut futures: Vec<_> = invocations
+        .iter()
+        .map(|invocation| Preparing::new(invocation, prepare(invocation)))
+        .collect();
+    poll_fn(|cx| {
+        // Poll every future, even if an earlier one is still pending.
+        let mut done = true;
+        for future in &mut futures {
+            done &= future.poll(cx);
+        }
+        if done {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await;
+
+    let mut failure = None;
+    let states: Vec<_> = futures
+        .iter_mut()
+        .map(|future| ok_or_record(future.take(), &mut failure))
+        .collect();
+    match failure {
+        None => Ok(states.into_iter().map(unwrap_prepared).collect()),
+        Some(mut failure) => {
+            for (invocation, state) in invocations.iter().zip(states) {
+                if let Some(tmp) = state {
+                    failure.record_abort(invocation.abort(tmp));
+                }
+            }
+            Err(failure)
+        }
+    }
+}
+
+/// Future preparing an invocation, which is joined with others. Remembers its output until all of
+/// them are done. Futures are boxed, so `Preparing` is `Unpin` and can be polled without pin
+/// projections.
+///
+/// A state which has not been taken is aborted on drop. This happens if the joined future is
+/// dropped before it completes, or if another future panics.
+struct Preparing<'a, I, F>
+where
+    I: AsyncInvocation,
+    F: Future<Output = Result<I::IntermediateState, Failure<I::Error>>>,
+{
+    invocation: &'a I,
+    future: MaybeDone<F>,
+}
+
+enum MaybeDone<F: Future> {
+    Pending(Pin<Box<F>>),
+    Done(F::Output),
+    Taken,
+}
+
+impl<'a, I, F> Preparing<'a, I, F>
+where
+    I: AsyncInvocation,
+    F: Future<Output = Result<I::IntermediateState, Failure<I::Error>>>,
+{
+    fn new(invocation: &'a I, future: F) -> Self {
+        Preparing {
+            invocation,
+            future: MaybeDone::Pending(Box::pin(future)),
+        }
+    }
+
+    /// Polls the future, unless it is done already. `true` if the future is done.
+    fn poll(&mut self, cx: &mut Context<'_>) -> bool {
+        if let MaybeDone::Pending(future) = &mut self.future {
+            match future.as_mut().poll(cx) {
+                Poll::Ready(output) => self.future = MaybeDone::Done(output),
+                Poll::Pending => return false,
+            }
+        }
+        true
+    }
+
+    fn take(&mut self) -> F::Output {
+        match std::mem::replace(&mut self.future, MaybeDone::Taken) {
+            MaybeDone::Done(output) => output,
+            _ => unreachable!("Output must only be taken once the future is done"),
+        }
+    }
+}
+
+impl<I, F> Drop for Preparing<'_, I, F>
+where
+    I: AsyncInvocation,
+    F: Future<Output = Result<I::IntermediateState, Failure<I::Error>>>,
+{
+    fn drop(&mut self) {
+        if let MaybeDone::Done(Ok(_)) = self.future {
+            if let Ok(tmp) = self.take() {
+                // There is no caller to report the error to.
+                let _ = self.invocation.abort(tmp);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::{Cell, RefCell},
+        future::Future,
+        pin::pin,
+        sync::Arc,
+        task::{Context, Poll, Wake, Waker},
+        thread::{self, Thread},
+    };
+
+    use crate::{
+        from_fns,
+        tests::{Constant, RejectOdd, Reserve},
+        Failure,
+    };
+
+    use super::{AsyncInvocation, Lift, SendAsyncInvocation};
+
+    /// Minimal executor, so we do not need to depend on an async runtime.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct Unpark(Thread);
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark()
+            }
+        }
+
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    /// Pending the first time it is polled.
+    async fn yield_now() {
+        let mut yielded = false;
+        std::future::poll_fn(|cx| {
+            if yielded {
+                Poll::Ready(())
+            } else {
+                yielded = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Logs when `may_fail` starts and ends. Yields in between.
+    struct Logged<'a> {
+        name: &'static str,
+        log: &'a RefCell<Vec<String>>,
+    }
+
+    impl AsyncInvocation for Logged<'_> {
+        type Error = ();
+        type Output = ();
+        type IntermediateState = ();
+
+        async fn may_fail(&self) -> Result<(), ()> {
+            self.log.borrow_mut().push(format!("start {}", self.name));
+            yield_now().await;
+            self.log.borrow_mut().push(format!("end {}", self.name));
+            Ok(())
+        }
+
+        fn commit(self, _: ()) {
+            self.log.borrow_mut().push(format!("commit {}", self.name));
+        }
+    }
+
+    #[test]
+    fn may_fail_of_elements_runs_concurrently() {
+        let log = RefCell::new(Vec::new());
+        let invocation = (
+            Logged {
+                name: "a",
+                log: &log,
+            },
+            vec![
+                Logged {
+                    name: "b",
+                    log: &log,
+                },
+                Logged {
+                    name: "c",
+                    log: &log,
+                },
+            ],
+        );
+
+        block_on(invocation.execute()).unwrap();
+
+        let expected = [
+            "start a", "start b", "start c", "end a", "end b", "end c", "commit a", "commit b",
+            "commit c",
+        ];
+        assert_eq!(expected.as_slice(), log.borrow().as_slice());
+    }
+
+    /// Never finishes `may_fail`.
+    struct Pending;
+
+    impl AsyncInvocation for Pending {
+        type Error = &'static str;
+        type Output = ();
+        type IntermediateState = ();
+
+        async fn may_fail(&self) -> Result<(), &'static str> {
+            std::future::pending().await
+        }
+
+        fn commit(self, _: ()) {}
+    }
+
+    #[test]
+    fn prepared_elements_are_aborted_if_future_is_dropped() {
+        let reserved = Cell::new(0);
+        let reserve = || {
+            Lift::new(Reserve {
+                reserved: &reserved,
+                abort_error: None,
+            })
+        };
+        let invocation = (reserve(), vec![reserve(), reserve()], (Pending,));
+
+        {
+            let mut future = pin!(invocation.execute());
+            let mut cx = Context::from_waker(Waker::noop());
+            assert!(future.as_mut().poll(&mut cx).is_pending());
+            assert_eq!(3, reserved.get());
+        }
+
+        assert_eq!(0, reserved.get());
+    }
+
+    #[test]
+    fn lift_sync_invocations() {
+        let invocation = (Lift::new(RejectOdd(2)), vec![Lift::new(RejectOdd(3))]);
+
+        assert_eq!(Err(3), block_on(invocation.execute()));
+        assert_eq!(Ok(42), block_on(Lift::new(Constant).execute()));
+    }
+
+    #[test]
+    fn abort_errors_are_reported_alongside_cause() {
+        let reserved = Cell::new(0);
+        let reserve = Reserve {
+            reserved: &reserved,
+            abort_error: Some("abort failed"),
+        };
+        let fail = from_fns(|| Err::<(), _>("failed"), |()| ());
+
+        let result = block_on((Lift::new(reserve), vec![Lift::new(fail)]).execute_detailed());
+
+        let expected = Failure {
+            cause: "failed",
+            path: Vec::new(),
+            abort_errors: vec!["abort failed"],
+        };
+        assert_eq!(Err(expected), result.map(|_| ()));
+        assert_eq!(0, reserved.get());
+    }
+
+    /// Executes the invocation on another thread, which only compiles if the future is `Send`.
+    fn execute_on_other_thread<I>(invocation: I) -> Result<I::Output, I::Error>
+    where
+        I: SendAsyncInvocation,
+    {
+        let future = invocation.execute_send();
+        thread::scope(|scope| scope.spawn(move || block_on(future)).join().unwrap())
+    }
+
+    #[test]
+    fn send_futures_of_generic_invocations() {
+        let invocation = (Lift::new(RejectOdd(2)), vec![Lift::new(RejectOdd(4))]);
+
+        assert_eq!(Ok((2, vec![4])), execute_on_other_thread(invocation));
+        assert_eq!(
+            Err(3),
+            execute_on_other_thread(vec![Lift::new(RejectOdd(3))])
+        );
+    }
+}
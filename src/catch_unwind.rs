@@ -0,0 +1,203 @@
+use std::{
+    any::Any,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+};
+
+use crate::{Failure, Invocation};
+
+/// Turns panics in `may_fail` and `abort` into errors. Created by [`Invocation::catch_unwind`].
+///
+/// Wrapping a whole composition catches a panic of any of its steps. The steps prepared before the
+/// panic are aborted while it unwinds through the composition, with errors of `abort` being
+/// discarded. Wrap the individual steps instead, to see the panic as an ordinary error, which is
+/// reported alongside abort errors. `commit` must not fail, so panics during commit are not caught.
+pub struct CatchUnwind<I> {
+    invocation: I,
+}
+
+impl<I> CatchUnwind<I> {
+    pub(crate) fn new(invocation: I) -> Self {
+        CatchUnwind { invocation }
+    }
+}
+
+impl<I> Invocation for CatchUnwind<I>
+where
+    I: Invocation,
+{
+    type Error = UnwindError<I::Error>;
+    type Output = I::Output;
+    type IntermediateState = I::IntermediateState;
+
+    fn may_fail(&self) -> Result<Self::IntermediateState, Self::Error> {
+        self.may_fail_detailed().map_err(|failure| failure.cause)
+    }
+
+    fn commit(self, tmp: Self::IntermediateState) -> Self::Output {
+        self.invocation.commit(tmp)
+    }
+
+    fn abort(&self, tmp: Self::IntermediateState) -> Result<(), Self::Error> {
+        // The invocation is not used again after a panic, so observing broken invariants is not an
+        // issue.
+        panic::catch_unwind(AssertUnwindSafe(|| self.invocation.abort(tmp)))
+            .map_err(|payload| UnwindError::Panic(PanicError { payload }))?
+            .map_err(UnwindError::Error)
+    }
+
+    fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<Self::Error>> {
+        panic::catch_unwind(AssertUnwindSafe(|| self.invocation.may_fail_detailed()))
+            .map_err(|payload| Failure::new(UnwindError::Panic(PanicError { payload })))?
+            .map_err(|failure| failure.map(UnwindError::Error))
+    }
+}
+
+/// Error of an invocation wrapped in [`CatchUnwind`].
+#[derive(Debug)]
+pub enum UnwindError<E> {
+    /// Error returned by the wrapped invocation.
+    Error(E),
+    /// The wrapped invocation panicked.
+    Panic(PanicError),
+}
+
+impl<E: fmt::Display> fmt::Display for UnwindError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnwindError::Error(error) => error.fmt(f),
+            UnwindError::Panic(panic) => panic.fmt(f),
+        }
+    }
+}
+
+impl<E> std::error::Error for UnwindError<E>
+where
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UnwindError::Error(error) => Some(error),
+            UnwindError::Panic(panic) => Some(panic),
+        }
+    }
+}
+
+/// A panic caught by [`CatchUnwind`].
+pub struct PanicError {
+    payload: Box<dyn Any + Send>,
+}
+
+impl PanicError {
+    /// Message passed to `panic!`, if the payload is a string.
+    pub fn message(&self) -> Option<&str> {
+        self.payload
+            .downcast_ref::<&'static str>()
+            .copied()
+            .or_else(|| self.payload.downcast_ref::<String>().map(String::as_str))
+    }
+
+    /// Payload of the panic, e.g. to continue unwinding with [`std::panic::resume_unwind`].
+    pub fn into_payload(self) -> Box<dyn Any + Send> {
+        self.payload
+    }
+}
+
+impl fmt::Debug for PanicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PanicError")
+            .field("message", &self.message())
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for PanicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.message() {
+            Some(message) => write!(f, "Invocation panicked: {message}"),
+            None => write!(f, "Invocation panicked"),
+        }
+    }
+}
+
+impl std::error::Error for PanicError {}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::{
+        from_fns,
+        tests::{Constant, Reserve},
+        Invocation,
+    };
+
+    use super::UnwindError;
+
+    #[test]
+    fn panic_becomes_error() {
+        let panics = from_fns(
+            || -> Result<(), ()> { panic!("bug in validation") },
+            |()| (),
+        );
+
+        match panics.catch_unwind().execute() {
+            Err(UnwindError::Panic(panic)) => {
+                assert_eq!(Some("bug in validation"), panic.message())
+            }
+            _ => panic!("Expected panic to be caught"),
+        }
+    }
+
+    #[test]
+    fn errors_and_outputs_are_passed_through() {
+        assert_eq!(42, Constant.catch_unwind().execute().unwrap());
+
+        let fails = from_fns(|| Err::<(), _>("failed"), |()| ());
+        assert!(matches!(
+            fails.catch_unwind().execute(),
+            Err(UnwindError::Error("failed"))
+        ));
+    }
+
+    #[test]
+    fn prepared_steps_are_aborted_if_later_step_panics() {
+        let reserved = Cell::new(0);
+        let reserve = Reserve {
+            reserved: &reserved,
+            abort_error: None,
+        };
+        let panics = from_fns(
+            || -> Result<(), &'static str> { panic!("bug in validation") },
+            |()| (),
+        );
+
+        let result = (reserve.catch_unwind(), panics.catch_unwind()).execute();
+
+        assert!(matches!(result, Err(UnwindError::Panic(_))));
+        assert_eq!(0, reserved.get());
+    }
+
+    #[test]
+    fn whole_composition_aborts_prepared_steps_on_panic() {
+        let reserved = Cell::new(0);
+        let reserve = || Reserve {
+            reserved: &reserved,
+            abort_error: None,
+        };
+        let panics = || {
+            from_fns(
+                || -> Result<(), &'static str> { panic!("bug in validation") },
+                |()| (),
+            )
+        };
+
+        let tuple = (reserve(), vec![reserve(), reserve()], panics()).catch_unwind();
+        assert!(matches!(tuple.execute(), Err(UnwindError::Panic(_))));
+        assert_eq!(0, reserved.get());
+
+        let nested = [(reserve(), reserve().and_then(|()| panics()))].catch_unwind();
+        assert!(matches!(nested.execute(), Err(UnwindError::Panic(_))));
+        assert_eq!(0, reserved.get());
+    }
+}
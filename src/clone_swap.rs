@@ -0,0 +1,88 @@
+use crate::Invocation;
+
+/// Offers strong exception safety for any fallible mutation of a `Clone` value, by applying the
+/// mutation to a copy in `may_fail` and swapping the copy in during `commit`. This allows legacy
+/// code of the form `fn(&mut T) -> Result<O, E>`, which might leave its argument in a half changed
+/// state on error, to take part in a chain of invocations. The price is a clone of the target.
+pub struct CloneSwap<'a, T, F> {
+    target: &'a mut T,
+    mutation: F,
+}
+
+impl<'a, T, F> CloneSwap<'a, T, F> {
+    pub fn new(target: &'a mut T, mutation: F) -> Self {
+        CloneSwap { target, mutation }
+    }
+}
+
+impl<T, F, O, E> Invocation for CloneSwap<'_, T, F>
+where
+    T: Clone,
+    F: Fn(&mut T) -> Result<O, E>,
+{
+    type Error = E;
+    type Output = O;
+    /// Mutated copy of the target and the output of the mutation.
+    type IntermediateState = (T, O);
+
+    fn may_fail(&self) -> Result<(T, O), E> {
+        let mut copy = self.target.clone();
+        let output = (self.mutation)(&mut copy)?;
+        Ok((copy, output))
+    }
+
+    fn commit(self, (mut copy, output): (T, O)) -> O {
+        std::mem::swap(self.target, &mut copy);
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Invocation;
+
+    use super::CloneSwap;
+
+    /// Does not offer strong exception safety. Items before the first negative one are appended
+    /// even in case of an error.
+    fn append_non_negative(target: &mut Vec<i32>, items: &[i32]) -> Result<(), i32> {
+        for &item in items {
+            if item < 0 {
+                return Err(item);
+            }
+            target.push(item);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn failing_mutation_leaves_target_unchanged() {
+        let mut first = vec![1];
+        let mut second = vec![2];
+
+        let result = (
+            CloneSwap::new(&mut first, |v: &mut Vec<i32>| append_non_negative(v, &[3])),
+            CloneSwap::new(&mut second, |v: &mut Vec<i32>| {
+                append_non_negative(v, &[4, -5])
+            }),
+        )
+            .execute();
+
+        assert_eq!(Err(-5), result.map(|_| ()));
+        assert_eq!(vec![1], first);
+        assert_eq!(vec![2], second);
+    }
+
+    #[test]
+    fn successful_mutation_is_swapped_in() {
+        let mut target = vec![1];
+
+        CloneSwap::new(&mut target, |v: &mut Vec<i32>| {
+            append_non_negative(v, &[2, 3])
+        })
+        .execute()
+        .unwrap();
+
+        assert_eq!(vec![1, 2, 3], target);
+    }
+}
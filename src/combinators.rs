@@ -0,0 +1,433 @@
+//! Adapters returned by the combinator methods of [`crate::Invocation`].
+
+use std::marker::PhantomData;
+
+use crate::{
+    failure::{abort_after_panic, abort_all, abort_on_panic, trace, unwrap_prepared},
+    ChainError, Failure, Invocation,
+};
+
+/// Transforms the output of an invocation. Created by [`Invocation::map`].
+pub struct Map<I, F> {
+    invocation: I,
+    f: F,
+}
+
+impl<I, F> Map<I, F> {
+    pub(crate) fn new(invocation: I, f: F) -> Self {
+        Map { invocation, f }
+    }
+}
+
+impl<I, F, O> Invocation for Map<I, F>
+where
+    I: Invocation,
+    F: FnOnce(I::Output) -> O,
+{
+    type Error = I::Error;
+    type Output = O;
+    type IntermediateState = I::IntermediateState;
+
+    fn may_fail(&self) -> Result<Self::IntermediateState, Self::Error> {
+        self.invocation.may_fail()
+    }
+
+    fn commit(self, tmp: Self::IntermediateState) -> Self::Output {
+        (self.f)(self.invocation.commit(tmp))
+    }
+
+    fn abort(&self, tmp: Self::IntermediateState) -> Result<(), Self::Error> {
+        self.invocation.abort(tmp)
+    }
+
+    fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<Self::Error>> {
+        self.invocation.may_fail_detailed()
+    }
+}
+
+/// Transforms the error of an invocation. Created by [`Invocation::map_err`].
+pub struct MapErr<I, F> {
+    invocation: I,
+    f: F,
+}
+
+impl<I, F> MapErr<I, F> {
+    pub(crate) fn new(invocation: I, f: F) -> Self {
+        MapErr { invocation, f }
+    }
+}
+
+impl<I, F, E> Invocation for MapErr<I, F>
+where
+    I: Invocation,
+    F: Fn(I::Error) -> E,
+{
+    type Error = E;
+    type Output = I::Output;
+    type IntermediateState = I::IntermediateState;
+
+    fn may_fail(&self) -> Result<Self::IntermediateState, Self::Error> {
+        self.invocation.may_fail().map_err(&self.f)
+    }
+
+    fn commit(self, tmp: Self::IntermediateState) -> Self::Output {
+        self.invocation.commit(tmp)
+    }
+
+    fn abort(&self, tmp: Self::IntermediateState) -> Result<(), Self::Error> {
+        self.invocation.abort(tmp).map_err(&self.f)
+    }
+
+    fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<Self::Error>> {
+        self.invocation
+            .may_fail_detailed()
+            .map_err(|failure| failure.map(&self.f))
+    }
+}
+
+/// Converts the error of an invocation into `E` using [`Into`]. Created by
+/// [`Invocation::err_into`].
+pub struct ErrInto<I, E> {
+    invocation: I,
+    // `fn() -> E` so `ErrInto` does not inherit auto traits from `E`, which it never holds.
+    _error: PhantomData<fn() -> E>,
+}
+
+impl<I, E> ErrInto<I, E> {
+    pub(crate) fn new(invocation: I) -> Self {
+        ErrInto {
+            invocation,
+            _error: PhantomData,
+        }
+    }
+}
+
+impl<I, E> Invocation for ErrInto<I, E>
+where
+    I: Invocation,
+    I::Error: Into<E>,
+{
+    type Error = E;
+    type Output = I::Output;
+    type IntermediateState = I::IntermediateState;
+
+    fn may_fail(&self) -> Result<Self::IntermediateState, Self::Error> {
+        self.invocation.may_fail().map_err(Into::into)
+    }
+
+    fn commit(self, tmp: Self::IntermediateState) -> Self::Output {
+        self.invocation.commit(tmp)
+    }
+
+    fn abort(&self, tmp: Self::IntermediateState) -> Result<(), Self::Error> {
+        self.invocation.abort(tmp).map_err(Into::into)
+    }
+
+    fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<Self::Error>> {
+        self.invocation
+            .may_fail_detailed()
+            .map_err(|failure| failure.map(Into::into))
+    }
+}
+
+/// Calls a function with a reference to the intermediate state, after `may_fail` succeeded.
+/// Created by [`Invocation::inspect_state`].
+pub struct InspectState<I, F> {
+    invocation: I,
+    f: F,
+}
+
+impl<I, F> InspectState<I, F> {
+    pub(crate) fn new(invocation: I, f: F) -> Self {
+        InspectState { invocation, f }
+    }
+}
+
+impl<I, F> Invocation for InspectState<I, F>
+where
+    I: Invocation,
+    F: Fn(&I::IntermediateState),
+{
+    type Error = I::Error;
+    type Output = I::Output;
+    type IntermediateState = I::IntermediateState;
+
+    fn may_fail(&self) -> Result<Self::IntermediateState, Self::Error> {
+        let tmp = self.invocation.may_fail()?;
+        (self.f)(&tmp);
+        Ok(tmp)
+    }
+
+    fn commit(self, tmp: Self::IntermediateState) -> Self::Output {
+        self.invocation.commit(tmp)
+    }
+
+    fn abort(&self, tmp: Self::IntermediateState) -> Result<(), Self::Error> {
+        self.invocation.abort(tmp)
+    }
+
+    fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<Self::Error>> {
+        let mut tmp = Some(self.invocation.may_fail_detailed()?);
+        abort_on_panic(
+            &mut tmp,
+            |tmp| (self.f)(tmp.as_ref().expect("State is only taken after a panic")),
+            |tmp| abort_after_panic(tmp.take().map(|tmp| (&self.invocation, tmp))),
+        );
+        Ok(unwrap_prepared(tmp))
+    }
+}
+
+/// Reports errors as [`ChainError`], identifying the failing invocation within a composition.
+/// Created by [`Invocation::traced`].
+///
+/// Positions are recorded for the invocations prepared on the thread calling `may_fail`, see
+/// [`Failure::at`]. If a composition prepares its elements on other threads, the path ends at that
+/// composition.
+pub struct Traced<I> {
+    invocation: I,
+}
+
+impl<I> Traced<I> {
+    pub(crate) fn new(invocation: I) -> Self {
+        Traced { invocation }
+    }
+}
+
+impl<I> Invocation for Traced<I>
+where
+    I: Invocation,
+{
+    type Error = ChainError<I::Error>;
+    type Output = I::Output;
+    type IntermediateState = I::IntermediateState;
+
+    fn may_fail(&self) -> Result<Self::IntermediateState, Self::Error> {
+        trace(|| self.invocation.may_fail_detailed()).map_err(|failure| ChainError {
+            path: failure.path,
+            source: failure.cause,
+        })
+    }
+
+    fn commit(self, tmp: Self::IntermediateState) -> Self::Output {
+        self.invocation.commit(tmp)
+    }
+
+    fn abort(&self, tmp: Self::IntermediateState) -> Result<(), Self::Error> {
+        self.invocation.abort(tmp).map_err(ChainError::new)
+    }
+
+    fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<Self::Error>> {
+        trace(|| self.invocation.may_fail_detailed()).map_err(|failure| {
+            let cause = ChainError {
+                path: failure.path,
+                source: failure.cause,
+            };
+            Failure {
+                cause,
+                path: Vec::new(),
+                abort_errors: failure
+                    .abort_errors
+                    .into_iter()
+                    .map(ChainError::new)
+                    .collect(),
+            }
+        })
+    }
+}
+
+/// Executes a second invocation, which is created from the intermediate state of the first one.
+/// Nothing is committed until `may_fail` of both invocations succeeded. Created by
+/// [`Invocation::and_then`].
+pub struct AndThen<I, F> {
+    invocation: I,
+    f: F,
+}
+
+impl<I, F> AndThen<I, F> {
+    pub(crate) fn new(invocation: I, f: F) -> Self {
+        AndThen { invocation, f }
+    }
+}
+
+impl<I, F, J> Invocation for AndThen<I, F>
+where
+    I: Invocation,
+    F: Fn(&I::IntermediateState) -> J,
+    J: Invocation<Error = I::Error>,
+{
+    type Error = I::Error;
+    type Output = (I::Output, J::Output);
+    type IntermediateState = (I::IntermediateState, J, J::IntermediateState);
+
+    fn may_fail(&self) -> Result<Self::IntermediateState, Self::Error> {
+        self.may_fail_detailed().map_err(|failure| failure.cause)
+    }
+
+    fn commit(self, (first, then, second): Self::IntermediateState) -> Self::Output {
+        (self.invocation.commit(first), then.commit(second))
+    }
+
+    fn abort(&self, (first, then, second): Self::IntermediateState) -> Result<(), Self::Error> {
+        abort_all([self.invocation.abort(first), then.abort(second)])
+    }
+
+    fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<Self::Error>> {
+        let mut first = Some(
+            self.invocation
+                .may_fail_detailed()
+                .map_err(|failure| failure.at(0))?,
+        );
+        let (then, second) = abort_on_panic(
+            &mut first,
+            |first| {
+                let then = (self.f)(first.as_ref().expect("State is only taken after a panic"));
+                let second = then.may_fail_detailed();
+                (then, second)
+            },
+            |first| abort_after_panic(first.take().map(|tmp| (&self.invocation, tmp))),
+        );
+        let first = unwrap_prepared(first);
+        match second {
+            Ok(second) => Ok((first, then, second)),
+            Err(failure) => {
+                let mut failure = failure.at(1);
+                failure.record_abort(self.invocation.abort(first));
+                Err(failure)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::{
+        from_fns,
+        tests::{Constant, RejectOdd},
+        ChainError, Invocation,
+    };
+
+    #[test]
+    fn map_output() {
+        let invocation = Constant.map(|answer| answer.to_string());
+
+        let output = invocation.execute();
+
+        assert_eq!(Ok("42".to_owned()), output)
+    }
+
+    /// `RejectOdd` and `Constant` could not be part of the same tuple, due to different error types.
+    #[test]
+    fn map_err_allows_combining_different_error_types() {
+        let invocation = (Constant.map_err(|()| -1), RejectOdd(3));
+
+        let output = invocation.execute();
+
+        assert_eq!(Err(3), output)
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum CombinedError {
+        Unit,
+        Odd(i32),
+    }
+
+    impl From<()> for CombinedError {
+        fn from(_: ()) -> Self {
+            CombinedError::Unit
+        }
+    }
+
+    impl From<i32> for CombinedError {
+        fn from(n: i32) -> Self {
+            CombinedError::Odd(n)
+        }
+    }
+
+    #[test]
+    fn err_into_common_error_type() {
+        let invocation = (
+            Constant.err_into::<CombinedError>(),
+            RejectOdd(3).err_into(),
+        );
+
+        let output = invocation.execute();
+
+        assert_eq!(Err(CombinedError::Odd(3)), output)
+    }
+
+    #[test]
+    fn traced_identifies_failing_element_in_loop() {
+        let invocations: Vec<_> = (0..100)
+            .map(|n| RejectOdd(if n == 57 { 1 } else { 2 }))
+            .collect();
+
+        let result = invocations.traced().execute();
+
+        let expected = ChainError {
+            path: vec![57],
+            source: 1,
+        };
+        assert_eq!(Err(expected), result.map(|_| ()));
+    }
+
+    #[test]
+    fn traced_nested_compositions() {
+        let invocation = (
+            RejectOdd(2),
+            (RejectOdd(4), vec![RejectOdd(6), RejectOdd(7)]),
+        );
+
+        let result = invocation.traced().execute();
+
+        assert_eq!(vec![1, 1, 1], result.unwrap_err().path);
+    }
+
+    #[test]
+    fn inspect_state_is_called_only_after_may_fail_succeeded() {
+        let calls = Cell::new(0);
+
+        let output = RejectOdd(4)
+            .inspect_state(|()| calls.set(calls.get() + 1))
+            .execute();
+        assert_eq!(Ok(4), output);
+        assert_eq!(1, calls.get());
+
+        let output = RejectOdd(5)
+            .inspect_state(|()| calls.set(calls.get() + 1))
+            .execute();
+        assert_eq!(Err(5), output);
+        assert_eq!(1, calls.get());
+    }
+
+    /// Withdraws `amount` from `account`, assuming its balance is `balance`.
+    fn withdraw(
+        account: &Cell<u32>,
+        balance: u32,
+        amount: u32,
+    ) -> impl Invocation<Error = &'static str, Output = (), IntermediateState = u32> + '_ {
+        from_fns(
+            move || balance.checked_sub(amount).ok_or("Insufficient funds"),
+            |new_balance| account.set(new_balance),
+        )
+    }
+
+    #[test]
+    fn second_step_validates_against_state_of_first() {
+        let account = Cell::new(10);
+
+        let first = withdraw(&account, account.get(), 6);
+        let both = first.and_then(|&balance| withdraw(&account, balance, 6));
+        let result = both.execute();
+
+        assert_eq!(Err("Insufficient funds"), result);
+        assert_eq!(10, account.get());
+
+        let first = withdraw(&account, account.get(), 6);
+        let both = first.and_then(|&balance| withdraw(&account, balance, 3));
+        both.execute().unwrap();
+
+        assert_eq!(1, account.get());
+    }
+}
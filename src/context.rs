@@ -0,0 +1,308 @@
+use crate::{
+    failure::{abort_all, abort_on_panic},
+    for_each_tuple, Failure,
+};
+
+/// Like [`crate::Invocation`], but rather than holding references to the state it changes, the
+/// invocation is handed a context in each phase. This allows several invocations changing the
+/// same context to be chained, which would not be possible if each of them held a `&mut`
+/// reference to it.
+///
+/// `may_fail` of every element in a chain sees the context as it has been before the chain was
+/// executed. Use [`crate::Shadow`] if later steps must observe the changes of earlier ones.
+pub trait ContextInvocation<Ctx: ?Sized>: Sized {
+    type Error;
+    type Output;
+    type IntermediateState;
+
+    fn may_fail(&self, ctx: &Ctx) -> Result<Self::IntermediateState, Self::Error>;
+
+    fn commit(self, tmp: Self::IntermediateState, ctx: &mut Ctx) -> Self::Output;
+
+    /// See [`crate::Invocation::abort`].
+    fn abort(&self, tmp: Self::IntermediateState, ctx: &Ctx) -> Result<(), Self::Error> {
+        let _ = ctx;
+        drop(tmp);
+        Ok(())
+    }
+
+    /// See [`crate::Invocation::may_fail_detailed`].
+    fn may_fail_detailed(
+        &self,
+        ctx: &Ctx,
+    ) -> Result<Self::IntermediateState, Failure<Self::Error>> {
+        self.may_fail(ctx).map_err(Failure::new)
+    }
+
+    fn execute(self, ctx: &mut Ctx) -> Result<Self::Output, Self::Error> {
+        let tmp = self.may_fail(ctx)?;
+        let output = self.commit(tmp, ctx);
+        Ok(output)
+    }
+
+    /// See [`crate::Invocation::execute_detailed`].
+    fn execute_detailed(self, ctx: &mut Ctx) -> Result<Self::Output, Failure<Self::Error>> {
+        let tmp = self.may_fail_detailed(ctx)?;
+        let output = self.commit(tmp, ctx);
+        Ok(output)
+    }
+}
+
+/// Implements [`ContextInvocation`] for a tuple of the given arity.
+macro_rules! impl_context_invocation_for_tuple {
+    ($($i:tt $F:ident),+) => {
+        /// Chains up to 16 invocations against the same context. `may_fail` of all elements is
+        /// executed before any of them is committed. If one element fails, the elements prepared
+        /// before it are aborted. Commits are applied in order.
+        impl<Ctx: ?Sized, E, $($F),+> ContextInvocation<Ctx> for ($($F,)+)
+        where
+            $($F: ContextInvocation<Ctx, Error = E>,)+
+        {
+            type Error = E;
+            type Output = ($($F::Output,)+);
+            type IntermediateState = ($($F::IntermediateState,)+);
+
+            fn may_fail(&self, ctx: &Ctx) -> Result<Self::IntermediateState, E> {
+                self.may_fail_detailed(ctx).map_err(|failure| failure.cause)
+            }
+
+            fn commit(self, tmp: Self::IntermediateState, ctx: &mut Ctx) -> Self::Output {
+                ($(self.$i.commit(tmp.$i, ctx),)+)
+            }
+
+            fn abort(&self, tmp: Self::IntermediateState, ctx: &Ctx) -> Result<(), E> {
+                abort_all([$(self.$i.abort(tmp.$i, ctx)),+])
+            }
+
+            fn may_fail_detailed(&self, ctx: &Ctx) -> Result<Self::IntermediateState, Failure<E>> {
+                let mut states = ($(None::<$F::IntermediateState>,)+);
+                let mut failure = None;
+                let abort_prepared = |states: &mut ($(Option<$F::IntermediateState>,)+)| {
+                    $(
+                        if let Some(tmp) = states.$i.take() {
+                            let _ = self.$i.abort(tmp, ctx);
+                        }
+                    )+
+                };
+                $(
+                    if failure.is_none() {
+                        let result = abort_on_panic(
+                            &mut states,
+                            |_| self.$i.may_fail_detailed(ctx),
+                            abort_prepared,
+                        );
+                        match result {
+                            Ok(tmp) => states.$i = Some(tmp),
+                            Err(error) => failure = Some(error.at($i)),
+                        }
+                    }
+                )+
+                match failure {
+                    None => Ok(($(states.$i.expect("All elements must have been prepared"),)+)),
+                    Some(mut failure) => {
+                        $(
+                            if let Some(tmp) = states.$i {
+                                failure.record_abort(self.$i.abort(tmp, ctx));
+                            }
+                        )+
+                        Err(failure)
+                    }
+                }
+            }
+        }
+    };
+}
+
+for_each_tuple!(impl_context_invocation_for_tuple);
+
+/// Invokes every element in order against the same context. `may_fail` of all elements is
+/// executed before any of them is committed.
+impl<Ctx: ?Sized, I> ContextInvocation<Ctx> for Vec<I>
+where
+    I: ContextInvocation<Ctx>,
+{
+    type Error = I::Error;
+    type Output = Vec<I::Output>;
+    type IntermediateState = Vec<I::IntermediateState>;
+
+    fn may_fail(&self, ctx: &Ctx) -> Result<Self::IntermediateState, Self::Error> {
+        self.may_fail_detailed(ctx).map_err(|failure| failure.cause)
+    }
+
+    fn commit(self, tmp: Self::IntermediateState, ctx: &mut Ctx) -> Self::Output {
+        self.into_iter()
+            .zip(tmp)
+            .map(|(invocation, tmp)| invocation.commit(tmp, ctx))
+            .collect()
+    }
+
+    fn abort(&self, tmp: Self::IntermediateState, ctx: &Ctx) -> Result<(), Self::Error> {
+        abort_all(
+            self.iter()
+                .zip(tmp)
+                .map(|(invocation, tmp)| invocation.abort(tmp, ctx)),
+        )
+    }
+
+    fn may_fail_detailed(
+        &self,
+        ctx: &Ctx,
+    ) -> Result<Self::IntermediateState, Failure<Self::Error>> {
+        let mut states = Vec::with_capacity(self.len());
+        for (index, invocation) in self.iter().enumerate() {
+            let result = abort_on_panic(
+                &mut states,
+                |_| invocation.may_fail_detailed(ctx),
+                |states| {
+                    // The panic is reported instead of errors raised while aborting.
+                    for (invocation, tmp) in self.iter().zip(states.drain(..)) {
+                        let _ = invocation.abort(tmp, ctx);
+                    }
+                },
+            );
+            match result {
+                Ok(tmp) => states.push(tmp),
+                Err(failure) => {
+                    let mut failure = failure.at(index);
+                    for (invocation, tmp) in self.iter().zip(states) {
+                        failure.record_abort(invocation.abort(tmp, ctx));
+                    }
+                    return Err(failure);
+                }
+            }
+        }
+        Ok(states)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        cell::Cell,
+        panic::{self, AssertUnwindSafe},
+    };
+
+    use super::ContextInvocation;
+
+    /// Adds to a balance, fails if the balance would become negative.
+    pub(crate) struct Deposit(pub(crate) i64);
+
+    impl ContextInvocation<i64> for Deposit {
+        type Error = &'static str;
+        type Output = i64;
+        type IntermediateState = ();
+
+        fn may_fail(&self, balance: &i64) -> Result<(), &'static str> {
+            if balance + self.0 < 0 {
+                Err("Insufficient funds")
+            } else {
+                Ok(())
+            }
+        }
+
+        fn commit(self, _: (), balance: &mut i64) -> i64 {
+            *balance += self.0;
+            *balance
+        }
+    }
+
+    /// Puts a hold on part of the balance during `may_fail`, which is released by `abort` or turned
+    /// into a withdrawal by `commit`.
+    struct Hold<'a> {
+        held: &'a Cell<i64>,
+        amount: i64,
+    }
+
+    impl ContextInvocation<i64> for Hold<'_> {
+        type Error = &'static str;
+        type Output = ();
+        type IntermediateState = ();
+
+        fn may_fail(&self, balance: &i64) -> Result<(), &'static str> {
+            if balance - self.held.get() < self.amount {
+                return Err("Insufficient funds");
+            }
+            self.held.set(self.held.get() + self.amount);
+            Ok(())
+        }
+
+        fn commit(self, _: (), balance: &mut i64) {
+            self.held.set(self.held.get() - self.amount);
+            *balance -= self.amount;
+        }
+
+        fn abort(&self, _: (), _: &i64) -> Result<(), &'static str> {
+            self.held.set(self.held.get() - self.amount);
+            Ok(())
+        }
+    }
+
+    /// Panics in `may_fail`.
+    struct Panics;
+
+    impl ContextInvocation<i64> for Panics {
+        type Error = &'static str;
+        type Output = ();
+        type IntermediateState = ();
+
+        fn may_fail(&self, _: &i64) -> Result<(), &'static str> {
+            panic!("bug in validation")
+        }
+
+        fn commit(self, _: (), _: &mut i64) {}
+    }
+
+    #[test]
+    fn panicking_step_aborts_prepared_steps() {
+        let mut balance = 10;
+        let held = Cell::new(0);
+        let hold = |amount| Hold {
+            held: &held,
+            amount,
+        };
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            (hold(3), vec![hold(4), hold(1)], Panics).execute(&mut balance)
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(0, held.get());
+        assert_eq!(10, balance);
+    }
+
+    #[test]
+    fn failing_step_aborts_prepared_steps() {
+        let mut balance = 10;
+        let held = Cell::new(0);
+        let hold = |amount| Hold {
+            held: &held,
+            amount,
+        };
+
+        let result = (vec![hold(3), hold(4)], Deposit(-20)).execute(&mut balance);
+
+        assert_eq!(Err("Insufficient funds"), result.map(|_| ()));
+        assert_eq!(0, held.get());
+        assert_eq!(10, balance);
+    }
+
+    #[test]
+    fn two_steps_changing_the_same_context() {
+        let mut balance = 10;
+
+        let result = (Deposit(5), Deposit(-20)).execute(&mut balance);
+
+        assert_eq!(Err("Insufficient funds"), result);
+        assert_eq!(10, balance);
+    }
+
+    #[test]
+    fn commits_are_applied_in_order() {
+        let mut balance = 10;
+
+        let outputs = vec![Deposit(5), Deposit(-3)].execute(&mut balance).unwrap();
+
+        assert_eq!(vec![15, 12], outputs);
+        assert_eq!(12, balance);
+    }
+}
@@ -0,0 +1,123 @@
+use std::any::Any;
+
+use crate::{Failure, Invocation};
+
+/// Object safe counterpart of [`Invocation`]. The intermediate state is type erased, so invocations
+/// of different types, but with the same error and output type, can be stored in one collection,
+/// e.g. `Vec<Box<dyn DynInvocation<E, O>>>`. Such a boxed invocation implements [`Invocation`]
+/// itself, so it can be used with all the usual compositions.
+///
+/// Implemented for every invocation with a `'static` intermediate state. The state is erased as
+/// `Box<dyn Any>` and recovered by downcasting, which requires `'static`. The invocation itself may
+/// borrow, but an invocation whose intermediate state borrows (e.g. holds a `&'a T` or a
+/// `std::cell::RefMut`) can not be boxed. Such an invocation has to move owned data into its
+/// state instead, e.g. by cloning what it borrows.
+pub trait DynInvocation<E, O> {
+    fn may_fail_boxed(&self) -> Result<Box<dyn Any>, E>;
+
+    fn commit_boxed(self: Box<Self>, tmp: Box<dyn Any>) -> O;
+
+    fn abort_boxed(&self, tmp: Box<dyn Any>) -> Result<(), E>;
+
+    fn may_fail_detailed_boxed(&self) -> Result<Box<dyn Any>, Failure<E>>;
+}
+
+impl<I> DynInvocation<I::Error, I::Output> for I
+where
+    I: Invocation,
+    I::IntermediateState: 'static,
+{
+    fn may_fail_boxed(&self) -> Result<Box<dyn Any>, I::Error> {
+        let tmp = self.may_fail()?;
+        Ok(Box::new(tmp))
+    }
+
+    fn commit_boxed(self: Box<Self>, tmp: Box<dyn Any>) -> I::Output {
+        self.commit(downcast::<I>(tmp))
+    }
+
+    fn abort_boxed(&self, tmp: Box<dyn Any>) -> Result<(), I::Error> {
+        self.abort(downcast::<I>(tmp))
+    }
+
+    fn may_fail_detailed_boxed(&self) -> Result<Box<dyn Any>, Failure<I::Error>> {
+        let tmp = self.may_fail_detailed()?;
+        Ok(Box::new(tmp))
+    }
+}
+
+/// Recovers the intermediate state created by `may_fail_boxed` of `I`.
+fn downcast<I>(tmp: Box<dyn Any>) -> I::IntermediateState
+where
+    I: Invocation,
+    I::IntermediateState: 'static,
+{
+    *tmp.downcast()
+        .expect("Intermediate state must stem from may_fail of the same invocation")
+}
+
+impl<'a, E, O> Invocation for Box<dyn DynInvocation<E, O> + 'a> {
+    type Error = E;
+    type Output = O;
+    type IntermediateState = Box<dyn Any>;
+
+    fn may_fail(&self) -> Result<Box<dyn Any>, E> {
+        (**self).may_fail_boxed()
+    }
+
+    fn commit(self, tmp: Box<dyn Any>) -> O {
+        self.commit_boxed(tmp)
+    }
+
+    fn abort(&self, tmp: Box<dyn Any>) -> Result<(), E> {
+        (**self).abort_boxed(tmp)
+    }
+
+    fn may_fail_detailed(&self) -> Result<Box<dyn Any>, Failure<E>> {
+        (**self).may_fail_detailed_boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::{from_fns, tests::Reserve, Invocation};
+
+    use super::DynInvocation;
+
+    #[test]
+    fn list_of_different_invocation_types() {
+        let counter = Cell::new(0);
+        let reserved = Cell::new(0);
+        let handlers: Vec<Box<dyn DynInvocation<&str, ()>>> = vec![
+            Box::new(from_fns(|| Ok(counter.get() + 1), |n| counter.set(n))),
+            Box::new(Reserve {
+                reserved: &reserved,
+                abort_error: None,
+            }),
+        ];
+
+        handlers.execute().unwrap();
+
+        assert_eq!(1, counter.get());
+        assert_eq!(1, reserved.get());
+    }
+
+    #[test]
+    fn failing_handler_aborts_others() {
+        let reserved = Cell::new(0);
+        let handlers: Vec<Box<dyn DynInvocation<&str, ()>>> = vec![
+            Box::new(Reserve {
+                reserved: &reserved,
+                abort_error: None,
+            }),
+            Box::new(from_fns(|| Err::<(), _>("failed"), |()| ())),
+        ];
+
+        let result = handlers.execute();
+
+        assert_eq!(Err("failed"), result.map(|_| ()));
+        assert_eq!(0, reserved.get());
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{Failure, Invocation};
+
+/// Holds one of two alternatives. As an invocation it dispatches to whichever branch is present,
+/// so branching logic can be part of a chain of invocations. Both branches must share the same
+/// error type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+impl<A, B> Invocation for Either<A, B>
+where
+    A: Invocation,
+    B: Invocation<Error = A::Error>,
+{
+    type Error = A::Error;
+    type Output = Either<A::Output, B::Output>;
+    type IntermediateState = Either<A::IntermediateState, B::IntermediateState>;
+
+    fn may_fail(&self) -> Result<Self::IntermediateState, Self::Error> {
+        match self {
+            Either::Left(a) => a.may_fail().map(Either::Left),
+            Either::Right(b) => b.may_fail().map(Either::Right),
+        }
+    }
+
+    fn commit(self, tmp: Self::IntermediateState) -> Self::Output {
+        match (self, tmp) {
+            (Either::Left(a), Either::Left(tmp)) => Either::Left(a.commit(tmp)),
+            (Either::Right(b), Either::Right(tmp)) => Either::Right(b.commit(tmp)),
+            _ => unreachable!("Intermediate state must stem from the same branch"),
+        }
+    }
+
+    fn abort(&self, tmp: Self::IntermediateState) -> Result<(), Self::Error> {
+        match (self, tmp) {
+            (Either::Left(a), Either::Left(tmp)) => a.abort(tmp),
+            (Either::Right(b), Either::Right(tmp)) => b.abort(tmp),
+            _ => unreachable!("Intermediate state must stem from the same branch"),
+        }
+    }
+
+    fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<Self::Error>> {
+        match self {
+            Either::Left(a) => a.may_fail_detailed().map(Either::Left),
+            Either::Right(b) => b.may_fail_detailed().map(Either::Right),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        tests::{Identity, RejectOdd},
+        Invocation,
+    };
+
+    use super::Either;
+
+    #[test]
+    fn dispatch_to_present_branch() {
+        let branch = |left| {
+            if left {
+                Either::Left(RejectOdd(2))
+            } else {
+                Either::Right(RejectOdd(3))
+            }
+        };
+
+        assert_eq!(Ok(Either::Left(2)), branch(true).execute());
+        assert_eq!(Err(3), branch(false).execute());
+    }
+
+    #[test]
+    fn branches_with_different_outputs_in_chain() {
+        let branch: Either<_, Identity<&str>> = Either::Left(Identity::new(1));
+
+        let output = (branch, Identity::new(())).execute();
+
+        assert_eq!(Ok((Either::Left(1), ())), output)
+    }
+}
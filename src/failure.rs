@@ -0,0 +1,211 @@
+use std::{
+    cell::Cell,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+};
+
+use crate::Invocation;
+
+/// Error reported by [`crate::Invocation::may_fail_detailed`]. Holds the error which caused
+/// `may_fail` to fail, together with any errors raised while aborting the parts of a composed
+/// invocation which had already been prepared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure<E> {
+    /// Error returned by the `may_fail` which failed.
+    pub cause: E,
+    /// Position of the invocation which caused the failure, within composed invocations. E.g.
+    /// `[1, 3]` for the fourth element of a `Vec`, which is the second element of a tuple. Only
+    /// recorded within [`crate::Invocation::traced`], so executing a composition does not allocate
+    /// on the heap just to remember where it failed. Empty otherwise, or if the failing invocation
+    /// is not part of a composition. Tracing is limited to the thread executing `may_fail` of the
+    /// traced invocation, see [`Failure::at`].
+    pub path: Vec<usize>,
+    /// Errors returned by `abort`, while releasing the intermediate states of parts which had been
+    /// prepared before the failure.
+    pub abort_errors: Vec<E>,
+}
+
+impl<E> Failure<E> {
+    pub fn new(cause: E) -> Self {
+        Failure {
+            cause,
+            path: Vec::new(),
+            abort_errors: Vec::new(),
+        }
+    }
+
+    /// Transforms the cause and all abort errors with `f`.
+    pub fn map<F, E2>(self, mut f: F) -> Failure<E2>
+    where
+        F: FnMut(E) -> E2,
+    {
+        Failure {
+            cause: f(self.cause),
+            path: self.path,
+            abort_errors: self.abort_errors.into_iter().map(f).collect(),
+        }
+    }
+
+    /// Marks the failure as caused by the element at `index` of a composed invocation. Call this
+    /// from `may_fail_detailed` of your own compositions. See [`Failure::path`].
+    ///
+    /// Does nothing, unless called on a thread which is executing `may_fail` of a
+    /// [`crate::Traced`] invocation. Whether positions are traced is a thread local setting, so a
+    /// composition which prepares its elements on other threads can not record positions within
+    /// them. Its own call to `at` is recorded, as long as it happens on the traced thread.
+    pub fn at(mut self, index: usize) -> Self {
+        if TRACING.get() {
+            self.path.insert(0, index);
+        }
+        self
+    }
+
+    /// Remembers the error, in case aborting failed.
+    pub(crate) fn record_abort(&mut self, result: Result<(), E>) {
+        if let Err(error) = result {
+            self.abort_errors.push(error)
+        }
+    }
+}
+
+thread_local! {
+    static TRACING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Records positions in [`Failure::path`] for failures created on this thread while `f` runs.
+pub(crate) fn trace<T>(f: impl FnOnce() -> T) -> T {
+    /// Restores the previous setting, even if `f` panics.
+    struct Restore(bool);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            TRACING.set(self.0)
+        }
+    }
+
+    let _restore = Restore(TRACING.replace(true));
+    f()
+}
+
+/// Consumes all results, so every state is aborted, even if aborting an earlier one failed. Returns
+/// the first error.
+pub(crate) fn abort_all<E>(results: impl IntoIterator<Item = Result<(), E>>) -> Result<(), E> {
+    let mut first_error = Ok(());
+    for result in results {
+        if first_error.is_ok() {
+            first_error = result;
+        }
+    }
+    first_error
+}
+
+/// Converts the result of preparing an element into an optional state, remembering the first
+/// error.
+pub(crate) fn ok_or_record<T, E>(result: Result<T, E>, first_error: &mut Option<E>) -> Option<T> {
+    match result {
+        Ok(tmp) => Some(tmp),
+        Err(error) => {
+            if first_error.is_none() {
+                *first_error = Some(error);
+            }
+            None
+        }
+    }
+}
+
+/// State of an element of a composition, once every element has been prepared.
+pub(crate) fn unwrap_prepared<T>(state: Option<T>) -> T {
+    state.expect("All elements must have been prepared")
+}
+
+/// Calls `prepare`, which may use the states prepared so far. Should it panic, `abort` releases
+/// them before the panic continues to unwind. Otherwise they would be dropped without being
+/// aborted, since `may_fail` of the composition never returns.
+pub(crate) fn abort_on_panic<S, T>(
+    prepared: &mut S,
+    prepare: impl FnOnce(&S) -> T,
+    abort: impl FnOnce(&mut S),
+) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(|| prepare(prepared))) {
+        Ok(result) => result,
+        Err(payload) => {
+            abort(prepared);
+            panic::resume_unwind(payload)
+        }
+    }
+}
+
+/// Aborts states while a panic unwinds. Errors are ignored, since the panic is reported instead.
+pub(crate) fn abort_after_panic<'a, I>(
+    prepared: impl IntoIterator<Item = (&'a I, I::IntermediateState)>,
+) where
+    I: Invocation + 'a,
+{
+    for (invocation, tmp) in prepared {
+        let _ = invocation.abort(tmp);
+    }
+}
+
+impl<E> From<E> for Failure<E> {
+    fn from(cause: E) -> Self {
+        Failure::new(cause)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Failure<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.cause)?;
+        for error in &self.abort_errors {
+            write!(f, "\nAdditionally aborting failed: {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<E> std::error::Error for Failure<E>
+where
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.cause)
+    }
+}
+
+/// Error of a chain of invocations, identifying the invocation in the chain which failed. See
+/// [`crate::Invocation::traced`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainError<E> {
+    /// Position of the failing invocation within the chain. See [`Failure::path`].
+    pub path: Vec<usize>,
+    pub source: E,
+}
+
+impl<E> ChainError<E> {
+    pub fn new(source: E) -> Self {
+        ChainError {
+            path: Vec::new(),
+            source,
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for ChainError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invocation at [")?;
+        for (n, index) in self.path.iter().enumerate() {
+            if n != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{index}")?;
+        }
+        write!(f, "] failed: {}", self.source)
+    }
+}
+
+impl<E> std::error::Error for ChainError<E>
+where
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
@@ -0,0 +1,80 @@
+use crate::Invocation;
+
+/// Creates an invocation from two closures. `may_fail` must not change application state, `commit`
+/// must not fail. Arguments are passed to the invocation by capturing them in the closures.
+///
+/// ```
+/// use strong_function::{from_fns, Invocation};
+///
+/// let mut balance: u32 = 10;
+/// let amount = 3;
+/// let current = balance;
+/// let withdraw = from_fns(
+///     || current.checked_sub(amount).ok_or("Insufficient funds"),
+///     |new_balance| balance = new_balance,
+/// );
+/// withdraw.execute().unwrap();
+///
+/// assert_eq!(7, balance);
+/// ```
+pub fn from_fns<M, C, T, E, O>(may_fail: M, commit: C) -> FromFns<M, C>
+where
+    M: Fn() -> Result<T, E>,
+    C: FnOnce(T) -> O,
+{
+    FromFns { may_fail, commit }
+}
+
+/// Invocation created from two closures. See [`from_fns`].
+pub struct FromFns<M, C> {
+    may_fail: M,
+    commit: C,
+}
+
+impl<M, C, T, E, O> Invocation for FromFns<M, C>
+where
+    M: Fn() -> Result<T, E>,
+    C: FnOnce(T) -> O,
+{
+    type Error = E;
+    type Output = O;
+    type IntermediateState = T;
+
+    fn may_fail(&self) -> Result<T, E> {
+        (self.may_fail)()
+    }
+
+    fn commit(self, tmp: T) -> O {
+        (self.commit)(tmp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Invocation;
+
+    use super::from_fns;
+
+    #[test]
+    fn trivial_succeeding_closures() {
+        let invocation = from_fns(|| Ok::<_, ()>(21), |half| half * 2);
+
+        let answer = invocation.execute();
+
+        assert_eq!(Ok(42), answer)
+    }
+
+    #[test]
+    fn nothing_is_committed_if_any_closure_fails() {
+        let mut log = Vec::new();
+
+        let output = (
+            from_fns(|| Ok("first"), |entry| log.push(entry)),
+            from_fns(|| Err::<&str, _>("second failed"), |_| ()),
+        )
+            .execute();
+
+        assert_eq!(Err("second failed"), output.map(|_| ()));
+        assert!(log.is_empty());
+    }
+}
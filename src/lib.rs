@@ -1,3 +1,56 @@
+mod alternatives;
+mod async_invocation;
+mod catch_unwind;
+mod clone_swap;
+mod combinators;
+mod context;
+mod dyn_invocation;
+mod either;
+mod failure;
+mod from_fns;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod prepared;
+mod quorum;
+mod retry;
+mod shadow;
+mod validate;
+
+pub mod observer;
+
+use std::array;
+
+use self::failure::{abort_after_panic, abort_all, abort_on_panic};
+
+pub use self::{
+    alternatives::{first_ok, FirstOk, OrElse},
+    async_invocation::{AsyncInvocation, Lift, SendAsyncInvocation},
+    catch_unwind::{CatchUnwind, PanicError, UnwindError},
+    clone_swap::CloneSwap,
+    combinators::{AndThen, ErrInto, InspectState, Map, MapErr, Traced},
+    context::ContextInvocation,
+    dyn_invocation::DynInvocation,
+    either::Either,
+    failure::{ChainError, Failure},
+    from_fns::{from_fns, FromFns},
+    prepared::Prepared,
+    quorum::Quorum,
+    retry::{Backoff, Retry},
+    shadow::Shadow,
+    validate::{ValidateAll, ValidationErrors},
+};
+
+#[cfg(feature = "parallel")]
+pub use self::parallel::ParExecute;
+
+/// Derives [`Invocation`] for structs whose fields are all invocations.
+#[cfg(feature = "macros")]
+pub use strong_function_macros::Invocation;
+
+/// Splits a function into the `may_fail` and `commit` phase of an invocation.
+#[cfg(feature = "macros")]
+pub use strong_function_macros::strong_function;
+
 /// An instance is associated with a specific invocation of a function offering storng execption
 /// safety guarantees. Implmenters of this function are encouraged to hold the arguments of the
 /// function invocation as members.
@@ -10,40 +63,371 @@ pub trait Invocation: Sized {
 
     fn commit(self, tmp: Self::IntermediateState) -> Self::Output;
 
+    /// Called with an intermediate state which is not going to be committed, e.g. because a later
+    /// step in a chain failed. Override this if the state holds resources like temporary files,
+    /// reservations or locks which must be released. By default the state is just dropped.
+    fn abort(&self, tmp: Self::IntermediateState) -> Result<(), Self::Error> {
+        drop(tmp);
+        Ok(())
+    }
+
+    /// Like `may_fail`, but in case of an error also reports errors which occurred while aborting
+    /// already prepared parts of a composed invocation. Composed invocations override this and
+    /// implement `may_fail` in terms of it.
+    fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<Self::Error>> {
+        self.may_fail().map_err(Failure::new)
+    }
+
     fn execute(self) -> Result<Self::Output, Self::Error> {
         let tmp = Self::may_fail(&self)?;
         let output = Self::commit(self, tmp);
         Ok(output)
     }
+
+    /// Like `execute`, but reports errors which occurred while aborting already prepared parts
+    /// alongside the original error.
+    fn execute_detailed(self) -> Result<Self::Output, Failure<Self::Error>> {
+        let tmp = Self::may_fail_detailed(&self)?;
+        let output = Self::commit(self, tmp);
+        Ok(output)
+    }
+
+    /// Executes only the `may_fail` phase. The returned handle can be committed or aborted later,
+    /// e.g. after asking the user for confirmation.
+    fn prepare(self) -> Result<Prepared<Self>, Self::Error> {
+        let tmp = Self::may_fail(&self)?;
+        Ok(Prepared::new(self, tmp))
+    }
+
+    /// Transforms the output of this invocation with `f`. `f` is called during commit.
+    fn map<F, O>(self, f: F) -> Map<Self, F>
+    where
+        F: FnOnce(Self::Output) -> O,
+    {
+        Map::new(self, f)
+    }
+
+    /// Transforms the error of this invocation with `f`. Useful to chain invocations with different
+    /// error types.
+    fn map_err<F, E>(self, f: F) -> MapErr<Self, F>
+    where
+        F: Fn(Self::Error) -> E,
+    {
+        MapErr::new(self, f)
+    }
+
+    /// Converts the error of this invocation into `E`. Invocations from different sources can be
+    /// chained by converting all of their errors into one common type.
+    fn err_into<E>(self) -> ErrInto<Self, E>
+    where
+        Self::Error: Into<E>,
+    {
+        ErrInto::new(self)
+    }
+
+    /// Calls `f` with the intermediate state, after `may_fail` succeeded.
+    fn inspect_state<F>(self, f: F) -> InspectState<Self, F>
+    where
+        F: Fn(&Self::IntermediateState),
+    {
+        InspectState::new(self, f)
+    }
+
+    /// Reports errors as [`ChainError`], which identifies the failing invocation by its position
+    /// within composed invocations, like tuples or `Vec`s. Wrap the outermost composition.
+    /// Positions are only recorded within `traced`, so other executions do not pay for them.
+    fn traced(self) -> Traced<Self> {
+        Traced::new(self)
+    }
+
+    /// Reports panics in `may_fail` and `abort` as [`UnwindError::Panic`]. Compositions abort the
+    /// steps prepared before a panic, so this may wrap a single step or a whole chain.
+    fn catch_unwind(self) -> CatchUnwind<Self> {
+        CatchUnwind::new(self)
+    }
+
+    /// Tries `other` if `may_fail` of this invocation fails. Only the alternative which succeeded
+    /// is committed. If both fail, both errors are reported. Use [`first_ok`] to choose between
+    /// more than two alternatives.
+    fn or_else<J>(self, other: J) -> OrElse<Self, J>
+    where
+        J: Invocation<Error = Self::Error>,
+    {
+        OrElse::new(self, other)
+    }
+
+    /// Chains a second invocation, which is created by `f` from the intermediate state of this one.
+    /// This allows the second invocation to validate against the changes the first one is going
+    /// to make. Nothing is committed until `may_fail` of both invocations succeeded.
+    fn and_then<F, J>(self, f: F) -> AndThen<Self, F>
+    where
+        F: Fn(&Self::IntermediateState) -> J,
+        J: Invocation<Error = Self::Error>,
+    {
+        AndThen::new(self, f)
+    }
+}
+
+/// Invokes `$m` once for each tuple arity from 1 to 16. Each invocation receives a comma separated
+/// list of `index TypeParameter` pairs.
+macro_rules! for_each_tuple {
+    ($m:ident) => {
+        $m!(0 F1);
+        $m!(0 F1, 1 F2);
+        $m!(0 F1, 1 F2, 2 F3);
+        $m!(0 F1, 1 F2, 2 F3, 3 F4);
+        $m!(0 F1, 1 F2, 2 F3, 3 F4, 4 F5);
+        $m!(0 F1, 1 F2, 2 F3, 3 F4, 4 F5, 5 F6);
+        $m!(0 F1, 1 F2, 2 F3, 3 F4, 4 F5, 5 F6, 6 F7);
+        $m!(0 F1, 1 F2, 2 F3, 3 F4, 4 F5, 5 F6, 6 F7, 7 F8);
+        $m!(0 F1, 1 F2, 2 F3, 3 F4, 4 F5, 5 F6, 6 F7, 7 F8, 8 F9);
+        $m!(0 F1, 1 F2, 2 F3, 3 F4, 4 F5, 5 F6, 6 F7, 7 F8, 8 F9, 9 F10);
+        $m!(0 F1, 1 F2, 2 F3, 3 F4, 4 F5, 5 F6, 6 F7, 7 F8, 8 F9, 9 F10, 10 F11);
+        $m!(0 F1, 1 F2, 2 F3, 3 F4, 4 F5, 5 F6, 6 F7, 7 F8, 8 F9, 9 F10, 10 F11, 11 F12);
+        $m!(0 F1, 1 F2, 2 F3, 3 F4, 4 F5, 5 F6, 6 F7, 7 F8, 8 F9, 9 F10, 10 F11, 11 F12, 12 F13);
+        $m!(
+            0 F1, 1 F2, 2 F3, 3 F4, 4 F5, 5 F6, 6 F7, 7 F8, 8 F9, 9 F10, 10 F11, 11 F12, 12 F13,
+            13 F14
+        );
+        $m!(
+            0 F1, 1 F2, 2 F3, 3 F4, 4 F5, 5 F6, 6 F7, 7 F8, 8 F9, 9 F10, 10 F11, 11 F12, 12 F13,
+            13 F14, 14 F15
+        );
+        $m!(
+            0 F1, 1 F2, 2 F3, 3 F4, 4 F5, 5 F6, 6 F7, 7 F8, 8 F9, 9 F10, 10 F11, 11 F12, 12 F13,
+            13 F14, 14 F15, 15 F16
+        );
+    };
 }
 
-/// Use this to chain two invocation, while maintaining strong exception safety guarantee. This
-/// only works if the error types of both functions are identical.
-impl<F1, F2> Invocation for (F1, F2)
+use for_each_tuple;
+
+/// Implements [`Invocation`] for a tuple of the given arity.
+macro_rules! impl_invocation_for_tuple {
+    ($($i:tt $F:ident),+) => {
+        /// Use this to chain up to 16 invocations, while maintaining strong exception safety
+        /// guarantee. `may_fail` of all elements is executed before any of them is committed. If
+        /// one element fails, the elements prepared before it are aborted. This only works if the
+        /// error types of all functions are identical. Use [`Invocation::err_into`] or
+        /// [`Invocation::map_err`] to chain invocations with different error types.
+        impl<E, $($F),+> Invocation for ($($F,)+)
+        where
+            $($F: Invocation<Error = E>,)+
+        {
+            type Error = E;
+            type Output = ($($F::Output,)+);
+            type IntermediateState = ($($F::IntermediateState,)+);
+
+            fn may_fail(&self) -> Result<Self::IntermediateState, Self::Error> {
+                self.may_fail_detailed().map_err(|failure| failure.cause)
+            }
+
+            fn commit(self, tmp: Self::IntermediateState) -> Self::Output {
+                ($(self.$i.commit(tmp.$i),)+)
+            }
+
+            fn abort(&self, tmp: Self::IntermediateState) -> Result<(), Self::Error> {
+                abort_all([$(self.$i.abort(tmp.$i)),+])
+            }
+
+            fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<E>> {
+                let mut states = ($(None::<$F::IntermediateState>,)+);
+                let mut failure = None;
+                let abort_prepared = |states: &mut ($(Option<$F::IntermediateState>,)+)| {
+                    $(
+                        if let Some(tmp) = states.$i.take() {
+                            let _ = self.$i.abort(tmp);
+                        }
+                    )+
+                };
+                $(
+                    if failure.is_none() {
+                        let result = abort_on_panic(
+                            &mut states,
+                            |_| self.$i.may_fail_detailed(),
+                            abort_prepared,
+                        );
+                        match result {
+                            Ok(tmp) => states.$i = Some(tmp),
+                            Err(error) => failure = Some(error.at($i)),
+                        }
+                    }
+                )+
+                match failure {
+                    None => Ok(($(states.$i.expect("All elements must have been prepared"),)+)),
+                    Some(mut failure) => {
+                        $(
+                            if let Some(tmp) = states.$i {
+                                failure.record_abort(self.$i.abort(tmp));
+                            }
+                        )+
+                        Err(failure)
+                    }
+                }
+            }
+        }
+    };
+}
+
+for_each_tuple!(impl_invocation_for_tuple);
+
+/// Invokes every element in order, while maintaining strong exception safety guarantee. Useful
+/// for calling the same function in a loop. `may_fail` of all elements is executed before any of
+/// them is committed.
+impl<I> Invocation for Vec<I>
+where
+    I: Invocation,
+{
+    type Error = I::Error;
+    type Output = Vec<I::Output>;
+    type IntermediateState = Vec<I::IntermediateState>;
+
+    fn may_fail(&self) -> Result<Self::IntermediateState, Self::Error> {
+        self.may_fail_detailed().map_err(|failure| failure.cause)
+    }
+
+    fn commit(self, tmp: Self::IntermediateState) -> Self::Output {
+        self.into_iter()
+            .zip(tmp)
+            .map(|(invocation, tmp)| invocation.commit(tmp))
+            .collect()
+    }
+
+    fn abort(&self, tmp: Self::IntermediateState) -> Result<(), Self::Error> {
+        abort_all(
+            self.iter()
+                .zip(tmp)
+                .map(|(invocation, tmp)| invocation.abort(tmp)),
+        )
+    }
+
+    fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<Self::Error>> {
+        let mut states = Vec::with_capacity(self.len());
+        for (index, invocation) in self.iter().enumerate() {
+            let result = abort_on_panic(
+                &mut states,
+                |_| invocation.may_fail_detailed(),
+                |states| abort_after_panic(self.iter().zip(states.drain(..))),
+            );
+            match result {
+                Ok(tmp) => states.push(tmp),
+                Err(failure) => {
+                    let mut failure = failure.at(index);
+                    for (invocation, tmp) in self.iter().zip(states) {
+                        failure.record_abort(invocation.abort(tmp));
+                    }
+                    return Err(failure);
+                }
+            }
+        }
+        Ok(states)
+    }
+}
+
+/// Invokes every element in order, while maintaining strong exception safety guarantee. In contrast
+/// to the implementation for `Vec` neither phase allocates on the heap. The only exception are
+/// errors raised by `abort`, which are collected in [`Failure::abort_errors`].
+impl<I, const N: usize> Invocation for [I; N]
 where
-    F1: Invocation,
-    F2: Invocation<Error = F1::Error>,
+    I: Invocation,
 {
-    type Error = F1::Error;
-    type Output = (F1::Output, F2::Output);
-    type IntermediateState = (F1::IntermediateState, F2::IntermediateState);
+    type Error = I::Error;
+    type Output = [I::Output; N];
+    type IntermediateState = [I::IntermediateState; N];
 
     fn may_fail(&self) -> Result<Self::IntermediateState, Self::Error> {
-        Ok((self.0.may_fail()?, self.1.may_fail()?))
+        self.may_fail_detailed().map_err(|failure| failure.cause)
     }
 
     fn commit(self, tmp: Self::IntermediateState) -> Self::Output {
-        (self.0.commit(tmp.0), self.1.commit(tmp.1))
+        let mut states = tmp.into_iter();
+        self.map(|invocation| invocation.commit(states.next().expect("Lengths are identical")))
+    }
+
+    fn abort(&self, tmp: Self::IntermediateState) -> Result<(), Self::Error> {
+        abort_all(
+            self.iter()
+                .zip(tmp)
+                .map(|(invocation, tmp)| invocation.abort(tmp)),
+        )
+    }
+
+    fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<Self::Error>> {
+        let mut states: [Option<I::IntermediateState>; N] = array::from_fn(|_| None);
+        let mut failure = None;
+        for (index, invocation) in self.iter().enumerate() {
+            let result = abort_on_panic(
+                &mut states,
+                |_| invocation.may_fail_detailed(),
+                |states| {
+                    abort_after_panic(self.iter().zip(states.iter_mut().map_while(Option::take)))
+                },
+            );
+            match result {
+                Ok(tmp) => states[index] = Some(tmp),
+                Err(error) => {
+                    failure = Some(error.at(index));
+                    break;
+                }
+            }
+        }
+        match failure {
+            None => Ok(states.map(|state| state.expect("All elements must have been prepared"))),
+            Some(mut failure) => {
+                for (invocation, state) in self.iter().zip(states) {
+                    if let Some(tmp) = state {
+                        failure.record_abort(invocation.abort(tmp));
+                    }
+                }
+                Err(failure)
+            }
+        }
+    }
+}
+
+/// A conditional step. `None` does nothing and can not fail.
+impl<I> Invocation for Option<I>
+where
+    I: Invocation,
+{
+    type Error = I::Error;
+    type Output = Option<I::Output>;
+    type IntermediateState = Option<I::IntermediateState>;
+
+    fn may_fail(&self) -> Result<Self::IntermediateState, Self::Error> {
+        self.as_ref().map(I::may_fail).transpose()
+    }
+
+    fn commit(self, tmp: Self::IntermediateState) -> Self::Output {
+        match (self, tmp) {
+            (Some(invocation), Some(tmp)) => Some(invocation.commit(tmp)),
+            (None, None) => None,
+            _ => unreachable!("Intermediate state must stem from the same invocation"),
+        }
+    }
+
+    fn abort(&self, tmp: Self::IntermediateState) -> Result<(), Self::Error> {
+        match (self, tmp) {
+            (Some(invocation), Some(tmp)) => invocation.abort(tmp),
+            (None, None) => Ok(()),
+            _ => unreachable!("Intermediate state must stem from the same invocation"),
+        }
+    }
+
+    fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<Self::Error>> {
+        self.as_ref().map(I::may_fail_detailed).transpose()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::cell::Cell;
+
     use super::*;
 
     /// Implements `Func` and always succeeds with `42`.
-    struct Constant;
-    struct DummyState;
+    pub(crate) struct Constant;
+    pub(crate) struct DummyState;
 
     impl Invocation for Constant {
         type Error = ();
@@ -60,7 +444,7 @@ mod tests {
     }
 
     /// Passes through argument unchanged. Move semantics.
-    struct Identity<A>(A);
+    pub(crate) struct Identity<A>(A);
     impl<A> Identity<A> {
         pub fn new(arg: A) -> Self {
             Identity(arg)
@@ -113,4 +497,195 @@ mod tests {
 
         assert_eq!("Hello, World!", result)
     }
+
+    #[test]
+    fn invocations_in_a_loop() {
+        let invocations = vec![Constant, Constant, Constant];
+
+        let results = invocations.execute().unwrap();
+
+        assert_eq!(vec![42, 42, 42], results)
+    }
+
+    /// Fails if the wrapped number is odd. Returns the number on success.
+    pub(crate) struct RejectOdd(pub(crate) i32);
+
+    impl Invocation for RejectOdd {
+        type Error = i32;
+        type Output = i32;
+        type IntermediateState = ();
+
+        fn may_fail(&self) -> Result<(), i32> {
+            if self.0 % 2 == 0 {
+                Ok(())
+            } else {
+                Err(self.0)
+            }
+        }
+
+        fn commit(self, _: ()) -> i32 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn loop_fails_with_first_error() {
+        let invocations = vec![RejectOdd(2), RejectOdd(3), RejectOdd(5)];
+
+        let result = invocations.execute();
+
+        assert_eq!(Err(3), result)
+    }
+
+    #[test]
+    fn single_element_tuple() {
+        let (result,) = (Constant,).execute().unwrap();
+
+        assert_eq!(42, result)
+    }
+
+    #[test]
+    fn flat_output_for_three_invocations() {
+        let invocation = (Constant, Identity::new("Hello"), Identity::new(1.5));
+
+        let output = invocation.execute();
+
+        assert_eq!(Ok((42, "Hello", 1.5)), output)
+    }
+
+    #[test]
+    fn tuple_fails_with_first_error() {
+        let invocation = (RejectOdd(2), RejectOdd(4), RejectOdd(7), RejectOdd(9));
+
+        let output = invocation.execute();
+
+        assert_eq!(Err(7), output)
+    }
+
+    #[test]
+    fn sixteen_invocations() {
+        let c = || Constant;
+        let invocation = (
+            c(),
+            c(),
+            c(),
+            c(),
+            c(),
+            c(),
+            c(),
+            c(),
+            c(),
+            c(),
+            c(),
+            c(),
+            c(),
+            c(),
+            c(),
+            c(),
+        );
+
+        let output = invocation.execute().unwrap();
+
+        assert_eq!(42, output.15)
+    }
+
+    #[test]
+    fn array_of_invocations() {
+        let invocations = [Identity::new(1), Identity::new(2), Identity::new(3)];
+
+        let output = invocations.execute();
+
+        assert_eq!(Ok([1, 2, 3]), output)
+    }
+
+    #[test]
+    fn array_fails_with_first_error() {
+        let invocations = [RejectOdd(2), RejectOdd(3), RejectOdd(5)];
+
+        let output = invocations.execute();
+
+        assert_eq!(Err(3), output)
+    }
+
+    /// Reserves a unit of some resource in `may_fail`. The reservation is released again if the
+    /// invocation is aborted. Optionally aborting fails.
+    pub(crate) struct Reserve<'a> {
+        pub(crate) reserved: &'a Cell<u32>,
+        pub(crate) abort_error: Option<&'static str>,
+    }
+
+    impl Invocation for Reserve<'_> {
+        type Error = &'static str;
+        type Output = ();
+        type IntermediateState = ();
+
+        fn may_fail(&self) -> Result<(), &'static str> {
+            self.reserved.set(self.reserved.get() + 1);
+            Ok(())
+        }
+
+        fn commit(self, _: ()) {}
+
+        fn abort(&self, _: ()) -> Result<(), &'static str> {
+            self.reserved.set(self.reserved.get() - 1);
+            self.abort_error.map_or(Ok(()), Err)
+        }
+    }
+
+    #[test]
+    fn failing_step_aborts_prepared_steps() {
+        let reserved = Cell::new(0);
+        let reserve = || Reserve {
+            reserved: &reserved,
+            abort_error: None,
+        };
+        let fail = from_fns(|| Err::<(), _>("failed"), |()| ());
+
+        let result = (reserve(), vec![reserve(), reserve()], fail).execute();
+
+        assert_eq!(Err("failed"), result.map(|_| ()));
+        assert_eq!(0, reserved.get());
+    }
+
+    #[test]
+    fn abort_errors_are_reported_alongside_cause() {
+        let reserved = Cell::new(0);
+        let reserve = Reserve {
+            reserved: &reserved,
+            abort_error: Some("abort failed"),
+        };
+        let fail = from_fns(|| Err::<(), _>("failed"), |()| ());
+
+        let result = [(reserve, fail)].execute_detailed();
+
+        let expected = Failure {
+            cause: "failed",
+            path: Vec::new(),
+            abort_errors: vec!["abort failed"],
+        };
+        assert_eq!(Err(expected), result.map(|_| ()));
+        assert_eq!(0, reserved.get());
+    }
+
+    #[test]
+    fn path_is_only_recorded_if_traced() {
+        let invocation = || [(RejectOdd(2), RejectOdd(3))];
+
+        assert_eq!(
+            Vec::<usize>::new(),
+            invocation().execute_detailed().unwrap_err().path
+        );
+        assert_eq!(
+            vec![0, 1],
+            invocation().traced().execute().unwrap_err().path
+        );
+    }
+
+    #[test]
+    fn optional_step() {
+        let notify = |enabled: bool| enabled.then_some(RejectOdd(3));
+
+        assert_eq!(Ok((2, None)), (RejectOdd(2), notify(false)).execute());
+        assert_eq!(Err(3), (RejectOdd(2), notify(true)).execute());
+    }
 }
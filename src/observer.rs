@@ -0,0 +1,206 @@
+//! Observer pattern, in which every handler can veto an event before any handler applies it.
+
+use std::{collections::TryReserveError, fmt};
+
+use crate::{DynInvocation, Invocation};
+
+type Handler<'h, Event, E, O> = Box<dyn Fn(&Event) -> Box<dyn DynInvocation<E, O> + 'h> + 'h>;
+
+/// Identifies a handler subscribed to a [`Subject`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+/// Notifies subscribed handlers of events. Each handler creates an invocation for an event.
+/// `may_fail` of every handler's invocation is executed before any of them is committed, so every
+/// handler can veto the event.
+pub struct Subject<'h, Event, E, O = ()> {
+    handlers: Vec<(SubscriptionId, Handler<'h, Event, E, O>)>,
+    next_id: u64,
+}
+
+impl<'h, Event, E, O> Subject<'h, Event, E, O> {
+    pub fn new() -> Self {
+        Subject {
+            handlers: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Invocation adding `handler` to the subscribers. Outputs the id required to unsubscribe the
+    /// handler again.
+    ///
+    /// Handlers are stored as [`DynInvocation`], so the intermediate state of the invocations they
+    /// create must be `'static`.
+    pub fn subscribe<F, I>(&mut self, handler: F) -> Subscribe<'_, 'h, Event, E, O>
+    where
+        F: Fn(&Event) -> I + 'h,
+        I: Invocation<Error = E, Output = O> + 'h,
+        I::IntermediateState: 'static,
+    {
+        let handler: Handler<'h, Event, E, O> = Box::new(move |event| Box::new(handler(event)));
+        Subscribe {
+            subject: self,
+            handler,
+        }
+    }
+
+    /// Invocation removing the handler identified by `id` from the subscribers.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> Unsubscribe<'_, 'h, Event, E, O> {
+        Unsubscribe { subject: self, id }
+    }
+
+    /// Invocations of all handlers for `event`. Allows to combine the notification with other
+    /// invocations.
+    pub fn notification(&self, event: &Event) -> Vec<Box<dyn DynInvocation<E, O> + 'h>> {
+        self.handlers
+            .iter()
+            .map(|(_, handler)| handler(event))
+            .collect()
+    }
+
+    /// Notifies all handlers of `event`. Either all handlers apply the event or none of them.
+    /// Outputs are returned in order of subscription.
+    pub fn notify(&self, event: &Event) -> Result<Vec<O>, E> {
+        self.notification(event).execute()
+    }
+
+    /// Number of subscribed handlers.
+    pub fn len(&self) -> usize {
+        self.handlers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+}
+
+impl<Event, E, O> Default for Subject<'_, Event, E, O> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Adds a handler to a [`Subject`]. Created by [`Subject::subscribe`].
+pub struct Subscribe<'s, 'h, Event, E, O> {
+    subject: &'s mut Subject<'h, Event, E, O>,
+    handler: Handler<'h, Event, E, O>,
+}
+
+impl<'h, Event, E, O> Invocation for Subscribe<'_, 'h, Event, E, O> {
+    type Error = TryReserveError;
+    type Output = SubscriptionId;
+    /// Storage for the handlers, with enough capacity to hold the new one.
+    type IntermediateState = Vec<(SubscriptionId, Handler<'h, Event, E, O>)>;
+
+    fn may_fail(&self) -> Result<Self::IntermediateState, TryReserveError> {
+        // Allocating is the only thing which can fail, so we do it up front.
+        let mut handlers = Vec::new();
+        handlers.try_reserve_exact(self.subject.handlers.len() + 1)?;
+        Ok(handlers)
+    }
+
+    fn commit(self, mut handlers: Self::IntermediateState) -> SubscriptionId {
+        let id = SubscriptionId(self.subject.next_id);
+        self.subject.next_id += 1;
+        handlers.append(&mut self.subject.handlers);
+        handlers.push((id, self.handler));
+        self.subject.handlers = handlers;
+        id
+    }
+}
+
+/// Removes a handler from a [`Subject`]. Created by [`Subject::unsubscribe`].
+pub struct Unsubscribe<'s, 'h, Event, E, O> {
+    subject: &'s mut Subject<'h, Event, E, O>,
+    id: SubscriptionId,
+}
+
+impl<Event, E, O> Invocation for Unsubscribe<'_, '_, Event, E, O> {
+    type Error = UnknownSubscription;
+    type Output = ();
+    /// Position of the handler.
+    type IntermediateState = usize;
+
+    fn may_fail(&self) -> Result<usize, UnknownSubscription> {
+        self.subject
+            .handlers
+            .iter()
+            .position(|(id, _)| *id == self.id)
+            .ok_or(UnknownSubscription(self.id))
+    }
+
+    fn commit(self, index: usize) {
+        drop(self.subject.handlers.remove(index));
+    }
+}
+
+/// Error returned if unsubscribing a handler which is not subscribed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownSubscription(pub SubscriptionId);
+
+impl fmt::Display for UnknownSubscription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "No handler is subscribed with id {}", self.0 .0)
+    }
+}
+
+impl std::error::Error for UnknownSubscription {}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::{from_fns, Invocation};
+
+    use super::Subject;
+
+    #[test]
+    fn any_handler_can_veto() {
+        let total = Cell::new(0);
+        let mut subject = Subject::new();
+        subject
+            .subscribe(|&amount: &u32| {
+                from_fns(move || Ok(amount), |amount| total.set(total.get() + amount))
+            })
+            .execute()
+            .unwrap();
+        subject
+            .subscribe(|&amount: &u32| {
+                from_fns(
+                    move || {
+                        if amount > 10 {
+                            Err("Too large")
+                        } else {
+                            Ok(())
+                        }
+                    },
+                    |()| (),
+                )
+            })
+            .execute()
+            .unwrap();
+
+        subject.notify(&5).unwrap();
+        let result = subject.notify(&11);
+
+        assert_eq!(Err("Too large"), result.map(|_| ()));
+        assert_eq!(5, total.get());
+    }
+
+    #[test]
+    fn unsubscribe() {
+        let calls = Cell::new(0);
+        let mut subject = Subject::new();
+        let id = subject
+            .subscribe(|&()| from_fns(|| Ok::<_, ()>(()), |()| calls.set(calls.get() + 1)))
+            .execute()
+            .unwrap();
+
+        subject.unsubscribe(id).execute().unwrap();
+        subject.notify(&()).unwrap();
+
+        assert_eq!(0, calls.get());
+        assert!(subject.is_empty());
+        assert!(subject.unsubscribe(id).execute().is_err());
+    }
+}
@@ -0,0 +1,300 @@
+use std::{
+    num::NonZeroUsize,
+    panic::resume_unwind,
+    thread::{self, ScopedJoinHandle},
+};
+
+use crate::{
+    failure::{abort_after_panic, abort_on_panic, ok_or_record, unwrap_prepared},
+    for_each_tuple, Failure, Invocation,
+};
+
+/// Executor for tuples and `Vec`s of invocations, which executes `may_fail` of the elements
+/// concurrently. Useful if validation is expensive, e.g. hashing or parsing. Only available with
+/// the `parallel` feature.
+pub trait ParExecute: Invocation {
+    /// Executes `may_fail` of all elements in parallel, using scoped threads. If all of them
+    /// succeed, the elements are committed one after another in order. Otherwise the prepared
+    /// elements are aborted and the error of the first failing element is returned.
+    fn par_execute(self) -> Result<Self::Output, Self::Error> {
+        self.par_execute_detailed().map_err(|failure| failure.cause)
+    }
+
+    /// Like `par_execute`, but reports errors which occurred while aborting prepared elements
+    /// alongside the original error. See [`Invocation::execute_detailed`]. Positions are only
+    /// traced on a single thread, so [`Failure::path`] is always empty.
+    fn par_execute_detailed(self) -> Result<Self::Output, Failure<Self::Error>>;
+}
+
+impl<I> ParExecute for Vec<I>
+where
+    I: Invocation + Sync,
+    I::IntermediateState: Send,
+    I::Error: Send,
+{
+    fn par_execute_detailed(self) -> Result<Self::Output, Failure<Self::Error>> {
+        if self.is_empty() {
+            return Ok(Vec::new());
+        }
+        let threads = thread::available_parallelism()
+            .map_or(1, NonZeroUsize::get)
+            .min(self.len());
+        let chunk_size = self.len().div_ceil(threads);
+        let chunks: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(|| prepare_chunk(chunk)))
+                .collect();
+            handles.into_iter().map(ScopedJoinHandle::join).collect()
+        });
+
+        let mut panic = None;
+        let chunks: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| ok_or_record(chunk, &mut panic))
+            .collect();
+        if let Some(payload) = panic {
+            for (chunk, results) in self.chunks(chunk_size).zip(chunks) {
+                abort_after_panic(prepared(chunk.iter().zip(results.into_iter().flatten())));
+            }
+            resume_unwind(payload)
+        }
+        let results = chunks.into_iter().flat_map(unwrap_prepared);
+
+        let mut failure = None;
+        let states: Vec<_> = results
+            .map(|result| ok_or_record(result, &mut failure))
+            .collect();
+        match failure {
+            None => Ok(self.commit(states.into_iter().map(unwrap_prepared).collect())),
+            Some(mut failure) => {
+                for (invocation, state) in self.iter().zip(states) {
+                    if let Some(tmp) = state {
+                        failure.record_abort(invocation.abort(tmp));
+                    }
+                }
+                Err(failure)
+            }
+        }
+    }
+}
+
+macro_rules! impl_par_execute_for_tuple {
+    ($($i:tt $F:ident),+) => {
+        impl<E, $($F),+> ParExecute for ($($F,)+)
+        where
+            E: Send,
+            $(
+                $F: Invocation<Error = E> + Sync,
+                $F::IntermediateState: Send,
+            )+
+        {
+            fn par_execute_detailed(self) -> Result<Self::Output, Failure<E>> {
+                let results = thread::scope(|scope| {
+                    let handles = ($(scope.spawn(|| self.$i.may_fail_detailed()),)+);
+                    ($(handles.$i.join(),)+)
+                });
+
+                let mut panic = None;
+                let results = ($(ok_or_record(results.$i, &mut panic),)+);
+                if let Some(payload) = panic {
+                    $(
+                        if let Some(Ok(tmp)) = results.$i {
+                            let _ = self.$i.abort(tmp);
+                        }
+                    )+
+                    resume_unwind(payload)
+                }
+                let results = ($(unwrap_prepared(results.$i),)+);
+
+                let mut failure = None;
+                let states = ($(ok_or_record(results.$i, &mut failure),)+);
+                match failure {
+                    None => Ok(self.commit(($(unwrap_prepared(states.$i),)+))),
+                    Some(mut failure) => {
+                        $(
+                            if let Some(tmp) = states.$i {
+                                failure.record_abort(self.$i.abort(tmp));
+                            }
+                        )+
+                        Err(failure)
+                    }
+                }
+            }
+        }
+    };
+}
+
+for_each_tuple!(impl_par_execute_for_tuple);
+
+/// Executes `may_fail` of every element of the chunk. Should one of them panic, the elements
+/// prepared before it are aborted.
+fn prepare_chunk<I: Invocation>(
+    chunk: &[I],
+) -> Vec<Result<I::IntermediateState, Failure<I::Error>>> {
+    let mut results = Vec::with_capacity(chunk.len());
+    for invocation in chunk {
+        let result = abort_on_panic(
+            &mut results,
+            |_| invocation.may_fail_detailed(),
+            |results| abort_after_panic(prepared(chunk.iter().zip(results.drain(..)))),
+        );
+        results.push(result);
+    }
+    results
+}
+
+/// Pairs of invocations and their states, skipping the elements which failed.
+fn prepared<'a, I: Invocation + 'a, E>(
+    results: impl IntoIterator<Item = (&'a I, Result<I::IntermediateState, E>)>,
+) -> impl Iterator<Item = (&'a I, I::IntermediateState)> {
+    results
+        .into_iter()
+        .filter_map(|(invocation, result)| Some((invocation, result.ok()?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        panic::{self, AssertUnwindSafe},
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Barrier, Mutex,
+        },
+        time::Duration,
+    };
+
+    use crate::{
+        from_fns,
+        tests::{Constant, RejectOdd},
+        Failure, Invocation,
+    };
+
+    use super::ParExecute;
+
+    /// Always prepared successfully, but fails to abort.
+    struct AbortFails;
+
+    impl Invocation for AbortFails {
+        type Error = &'static str;
+        type Output = ();
+        type IntermediateState = ();
+
+        fn may_fail(&self) -> Result<(), &'static str> {
+            Ok(())
+        }
+
+        fn commit(self, _: ()) {}
+
+        fn abort(&self, _: ()) -> Result<(), &'static str> {
+            Err("abort failed")
+        }
+    }
+
+    /// Thread safe counterpart of `Reserve`, which panics instead of reserving if `panics` is set.
+    struct ReserveAtomic<'a> {
+        reserved: &'a AtomicU32,
+        panics: bool,
+    }
+
+    impl Invocation for ReserveAtomic<'_> {
+        type Error = &'static str;
+        type Output = ();
+        type IntermediateState = ();
+
+        fn may_fail(&self) -> Result<(), &'static str> {
+            assert!(!self.panics, "bug in validation");
+            self.reserved.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn commit(self, _: ()) {}
+
+        fn abort(&self, _: ()) -> Result<(), &'static str> {
+            self.reserved.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn may_fail_runs_concurrently() {
+        // Would dead lock, if `may_fail` of both elements ran on the same thread.
+        let barrier = Barrier::new(2);
+        let wait = || {
+            from_fns(
+                || {
+                    barrier.wait();
+                    Ok::<_, ()>(())
+                },
+                |()| (),
+            )
+        };
+
+        let output = (wait(), wait()).par_execute();
+
+        assert_eq!(Ok(((), ())), output)
+    }
+
+    #[test]
+    fn commit_in_order() {
+        let log = Mutex::new(Vec::new());
+        let invocations: Vec<_> = (0..20)
+            .map(|n| {
+                let log = &log;
+                from_fns(
+                    move || {
+                        // Finish validation in reverse order
+                        std::thread::sleep(Duration::from_millis(20 - n));
+                        Ok::<_, ()>(n)
+                    },
+                    move |n| log.lock().unwrap().push(n),
+                )
+            })
+            .collect();
+
+        invocations.par_execute().unwrap();
+
+        assert_eq!((0..20).collect::<Vec<_>>(), *log.lock().unwrap());
+    }
+
+    #[test]
+    fn first_error_is_reported() {
+        let invocations = vec![RejectOdd(2), RejectOdd(3), RejectOdd(5)];
+
+        assert_eq!(Err(3), invocations.par_execute());
+        assert_eq!(Ok((42,)), (Constant,).par_execute());
+    }
+
+    #[test]
+    fn abort_errors_are_reported_alongside_cause() {
+        let fail = from_fns(|| Err::<(), _>("failed"), |()| ());
+
+        let result = (AbortFails, fail).par_execute_detailed();
+
+        let expected = Failure {
+            cause: "failed",
+            path: Vec::new(),
+            abort_errors: vec!["abort failed"],
+        };
+        assert_eq!(Err(expected), result.map(|_| ()));
+    }
+
+    #[test]
+    fn prepared_elements_are_aborted_if_one_panics() {
+        let reserved = AtomicU32::new(0);
+        let reserve = |panics| ReserveAtomic {
+            reserved: &reserved,
+            panics,
+        };
+
+        let tuple = (reserve(false), reserve(true), reserve(false));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| tuple.par_execute()));
+        assert!(result.is_err());
+        assert_eq!(0, reserved.load(Ordering::SeqCst));
+
+        let invocations: Vec<_> = (0..20).map(|n| reserve(n == 13)).collect();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| invocations.par_execute()));
+        assert!(result.is_err());
+        assert_eq!(0, reserved.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,108 @@
+use crate::Invocation;
+
+/// An invocation whose `may_fail` phase has succeeded, but which has not been committed yet.
+/// Created by [`Invocation::prepare`]. Application state is left unchanged until
+/// [`Prepared::commit`] is called.
+pub struct Prepared<I: Invocation> {
+    // Only `None` after being committed or aborted.
+    inner: Option<(I, I::IntermediateState)>,
+}
+
+impl<I: Invocation> Prepared<I> {
+    pub(crate) fn new(invocation: I, state: I::IntermediateState) -> Self {
+        Prepared {
+            inner: Some((invocation, state)),
+        }
+    }
+
+    /// The invocation which has been prepared.
+    pub fn invocation(&self) -> &I {
+        &self.inner().0
+    }
+
+    /// Intermediate state produced by `may_fail`. Allows to inspect the planned change before
+    /// committing it.
+    pub fn state(&self) -> &I::IntermediateState {
+        &self.inner().1
+    }
+
+    /// Applies the planned change. Can not fail.
+    pub fn commit(mut self) -> I::Output {
+        let (invocation, state) = self.take();
+        invocation.commit(state)
+    }
+
+    /// Discards the planned change, leaving application state unchanged. Resources held by the
+    /// intermediate state are released using [`Invocation::abort`]. Dropping a `Prepared` has the
+    /// same effect, yet errors during abort are ignored.
+    pub fn abort(mut self) -> Result<(), I::Error> {
+        let (invocation, state) = self.take();
+        invocation.abort(state)
+    }
+
+    fn inner(&self) -> &(I, I::IntermediateState) {
+        self.inner
+            .as_ref()
+            .expect("Prepared is only empty after being consumed")
+    }
+
+    fn take(&mut self) -> (I, I::IntermediateState) {
+        self.inner
+            .take()
+            .expect("Prepared is only empty after being consumed")
+    }
+}
+
+impl<I: Invocation> Drop for Prepared<I> {
+    fn drop(&mut self) {
+        if let Some((invocation, state)) = self.inner.take() {
+            // There is no one to report the error to.
+            let _ = invocation.abort(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::{from_fns, tests::Reserve, Invocation};
+
+    #[test]
+    fn inspect_state_before_commit() {
+        let target = Cell::new(1);
+        let invocation = from_fns(|| Ok::<_, ()>(target.get() + 1), |n| target.set(n));
+
+        let prepared = invocation.prepare().unwrap();
+        assert_eq!(2, *prepared.state());
+        assert_eq!(1, target.get());
+        prepared.commit();
+
+        assert_eq!(2, target.get());
+    }
+
+    #[test]
+    fn abort_leaves_state_unchanged() {
+        let target = Cell::new(1);
+        let invocation = from_fns(|| Ok::<_, ()>(target.get() + 1), |n| target.set(n));
+
+        invocation.prepare().unwrap().abort().unwrap();
+
+        assert_eq!(1, target.get());
+    }
+
+    #[test]
+    fn dropping_prepared_releases_resources() {
+        let reserved = Cell::new(0);
+        let invocation = Reserve {
+            reserved: &reserved,
+            abort_error: None,
+        };
+
+        let prepared = invocation.prepare().unwrap();
+        assert_eq!(1, reserved.get());
+        drop(prepared);
+
+        assert_eq!(0, reserved.get());
+    }
+}
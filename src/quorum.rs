@@ -0,0 +1,140 @@
+use crate::{
+    failure::{abort_after_panic, abort_all, abort_on_panic},
+    Failure, Invocation,
+};
+
+/// Succeeds if at least `required` of its members can be prepared, e.g. for replicated writes
+/// which must reach a majority of replicas. Exactly the members which have been prepared are
+/// committed. Members whose `may_fail` failed are left alone. If the quorum is not reached, all
+/// prepared members are aborted.
+pub struct Quorum<I> {
+    required: usize,
+    members: Vec<I>,
+}
+
+impl<I> Quorum<I> {
+    pub fn new(required: usize, members: Vec<I>) -> Self {
+        Quorum { required, members }
+    }
+}
+
+impl<I> Invocation for Quorum<I>
+where
+    I: Invocation,
+{
+    /// Errors of every failing member together with its position, if the quorum is not reached.
+    type Error = Vec<(usize, I::Error)>;
+    /// Outputs of the committed members together with their positions.
+    type Output = Vec<(usize, I::Output)>;
+    type IntermediateState = Vec<(usize, I::IntermediateState)>;
+
+    fn may_fail(&self) -> Result<Self::IntermediateState, Self::Error> {
+        self.may_fail_detailed().map_err(|failure| failure.cause)
+    }
+
+    fn commit(self, tmp: Self::IntermediateState) -> Self::Output {
+        let mut members = self.members.into_iter().enumerate();
+        tmp.into_iter()
+            .map(|(index, tmp)| {
+                let (_, member) = members
+                    .find(|(position, _)| *position == index)
+                    .expect("Intermediate state must stem from the same invocation");
+                (index, member.commit(tmp))
+            })
+            .collect()
+    }
+
+    fn abort(&self, tmp: Self::IntermediateState) -> Result<(), Self::Error> {
+        abort_all(tmp.into_iter().map(|(index, tmp)| {
+            self.members[index]
+                .abort(tmp)
+                .map_err(|error| vec![(index, error)])
+        }))
+    }
+
+    fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<Self::Error>> {
+        let mut prepared = Vec::new();
+        let mut errors = Vec::new();
+        let mut abort_errors = Vec::new();
+        for (index, member) in self.members.iter().enumerate() {
+            let result = abort_on_panic(
+                &mut prepared,
+                |_| member.may_fail_detailed(),
+                |prepared| {
+                    abort_after_panic(
+                        prepared
+                            .drain(..)
+                            .map(|(index, tmp)| (&self.members[index], tmp)),
+                    )
+                },
+            );
+            match result {
+                Ok(tmp) => prepared.push((index, tmp)),
+                Err(failure) => {
+                    errors.push((index, failure.cause));
+                    abort_errors.extend(
+                        failure
+                            .abort_errors
+                            .into_iter()
+                            .map(|error| vec![(index, error)]),
+                    );
+                }
+            }
+        }
+        if prepared.len() >= self.required {
+            return Ok(prepared);
+        }
+        for (index, tmp) in prepared {
+            if let Err(error) = self.members[index].abort(tmp) {
+                abort_errors.push(vec![(index, error)]);
+            }
+        }
+        let mut failure = Failure::new(errors);
+        failure.abort_errors = abort_errors;
+        Err(failure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::{
+        tests::{RejectOdd, Reserve},
+        Invocation,
+    };
+
+    use super::Quorum;
+
+    #[test]
+    fn commit_members_which_could_be_prepared() {
+        let quorum = Quorum::new(2, vec![RejectOdd(2), RejectOdd(3), RejectOdd(4)]);
+
+        let output = quorum.execute();
+
+        assert_eq!(Ok(vec![(0, 2), (2, 4)]), output)
+    }
+
+    #[test]
+    fn fail_if_quorum_is_not_reached() {
+        let quorum = Quorum::new(2, vec![RejectOdd(2), RejectOdd(3), RejectOdd(5)]);
+
+        let output = quorum.execute();
+
+        assert_eq!(Err(vec![(1, 3), (2, 5)]), output)
+    }
+
+    #[test]
+    fn prepared_members_are_aborted_if_quorum_is_not_reached() {
+        let reserved = Cell::new(0);
+        let reserve = || Reserve {
+            reserved: &reserved,
+            abort_error: None,
+        };
+
+        let output = Quorum::new(3, vec![reserve(), reserve()]).execute();
+
+        assert_eq!(Err(vec![]), output.map(|_| ()));
+        assert_eq!(0, reserved.get());
+    }
+}
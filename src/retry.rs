@@ -0,0 +1,217 @@
+use std::{thread, time::Duration};
+
+use crate::{Failure, Invocation};
+
+/// Repeats `may_fail` of an invocation, if it fails with a transient error. This is safe, since a
+/// failing `may_fail` must not have changed anything. Only the error of the last attempt is
+/// reported.
+pub struct Retry<I, P> {
+    invocation: I,
+    max_attempts: u32,
+    backoff: Backoff,
+    retry_if: P,
+}
+
+impl<I> Retry<I, fn(&I::Error) -> bool>
+where
+    I: Invocation,
+{
+    /// Makes up to three attempts without waiting in between and retries on every error.
+    pub fn new(invocation: I) -> Self {
+        Retry {
+            invocation,
+            max_attempts: 3,
+            backoff: Backoff::None,
+            retry_if: |_| true,
+        }
+    }
+}
+
+impl<I, P> Retry<I, P> {
+    /// Total number of times `may_fail` is called at most, including the first one. At least one
+    /// attempt is always made.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// How long to wait before each retry.
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Only retries if `retry_if` returns `true` for the error. Other errors are reported
+    /// immediately.
+    pub fn retry_if<Q>(self, retry_if: Q) -> Retry<I, Q> {
+        Retry {
+            invocation: self.invocation,
+            max_attempts: self.max_attempts,
+            backoff: self.backoff,
+            retry_if,
+        }
+    }
+}
+
+impl<I, P> Invocation for Retry<I, P>
+where
+    I: Invocation,
+    P: Fn(&I::Error) -> bool,
+{
+    type Error = I::Error;
+    type Output = I::Output;
+    type IntermediateState = I::IntermediateState;
+
+    fn may_fail(&self) -> Result<Self::IntermediateState, Self::Error> {
+        self.may_fail_detailed().map_err(|failure| failure.cause)
+    }
+
+    fn commit(self, tmp: Self::IntermediateState) -> Self::Output {
+        self.invocation.commit(tmp)
+    }
+
+    fn abort(&self, tmp: Self::IntermediateState) -> Result<(), Self::Error> {
+        self.invocation.abort(tmp)
+    }
+
+    fn may_fail_detailed(&self) -> Result<Self::IntermediateState, Failure<Self::Error>> {
+        // Errors raised while aborting are reported, even if they stem from an earlier attempt.
+        let mut abort_errors = Vec::new();
+        let mut attempt = 1;
+        loop {
+            match self.invocation.may_fail_detailed() {
+                Ok(tmp) => return Ok(tmp),
+                Err(mut failure) => {
+                    abort_errors.append(&mut failure.abort_errors);
+                    if attempt >= self.max_attempts || !(self.retry_if)(&failure.cause) {
+                        failure.abort_errors = abort_errors;
+                        return Err(failure);
+                    }
+                }
+            }
+            thread::sleep(self.backoff.delay(attempt));
+            attempt += 1;
+        }
+    }
+}
+
+/// How long [`Retry`] waits between two attempts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+    /// Retry immediately.
+    None,
+    /// Wait the same duration before every retry.
+    Fixed(Duration),
+    /// Wait `initial` before the first retry and double the delay for each further one, up to
+    /// `max`.
+    Exponential { initial: Duration, max: Duration },
+}
+
+impl Backoff {
+    /// Delay before the retry following the failed attempt with number `attempt`, starting at 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        match *self {
+            Backoff::None => Duration::ZERO,
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, max } => 2u32
+                .checked_pow(attempt.saturating_sub(1))
+                .and_then(|factor| initial.checked_mul(factor))
+                .map_or(max, |delay| delay.min(max)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, time::Duration};
+
+    use crate::{tests::Constant, Invocation};
+
+    use super::{Backoff, Retry};
+
+    /// Fails with the number of the attempt, until `may_fail` has been called `succeed_at` times.
+    struct Flaky<'a> {
+        attempts: &'a Cell<u32>,
+        succeed_at: u32,
+    }
+
+    impl Invocation for Flaky<'_> {
+        type Error = u32;
+        type Output = u32;
+        type IntermediateState = ();
+
+        fn may_fail(&self) -> Result<(), u32> {
+            self.attempts.set(self.attempts.get() + 1);
+            if self.attempts.get() < self.succeed_at {
+                Err(self.attempts.get())
+            } else {
+                Ok(())
+            }
+        }
+
+        fn commit(self, _: ()) -> u32 {
+            self.attempts.get()
+        }
+    }
+
+    #[test]
+    fn succeeds_after_transient_failures() {
+        let attempts = Cell::new(0);
+        let flaky = Flaky {
+            attempts: &attempts,
+            succeed_at: 3,
+        };
+
+        assert_eq!(Ok(3), Retry::new(flaky).execute());
+    }
+
+    #[test]
+    fn reports_error_of_last_attempt() {
+        let attempts = Cell::new(0);
+        let flaky = Flaky {
+            attempts: &attempts,
+            succeed_at: 10,
+        };
+
+        assert_eq!(Err(4), Retry::new(flaky).max_attempts(4).execute());
+    }
+
+    #[test]
+    fn does_not_retry_rejected_errors() {
+        let attempts = Cell::new(0);
+        let flaky = Flaky {
+            attempts: &attempts,
+            succeed_at: 10,
+        };
+
+        let retry = Retry::new(flaky).retry_if(|attempt: &u32| *attempt < 2);
+
+        assert_eq!(Err(2), retry.execute());
+    }
+
+    #[test]
+    fn retry_within_tuple() {
+        let attempts = Cell::new(0);
+        let flaky = Flaky {
+            attempts: &attempts,
+            succeed_at: 2,
+        };
+
+        let invocation = (Constant.map_err(|()| 0), Retry::new(flaky));
+
+        assert_eq!(Ok((42, 2)), invocation.execute());
+    }
+
+    #[test]
+    fn exponential_backoff_is_capped() {
+        let backoff = Backoff::Exponential {
+            initial: Duration::from_millis(10),
+            max: Duration::from_millis(50),
+        };
+
+        assert_eq!(Duration::from_millis(10), backoff.delay(1));
+        assert_eq!(Duration::from_millis(40), backoff.delay(3));
+        assert_eq!(Duration::from_millis(50), backoff.delay(4));
+        assert_eq!(Duration::from_millis(50), backoff.delay(100));
+    }
+}
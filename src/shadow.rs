@@ -0,0 +1,124 @@
+use std::marker::PhantomData;
+
+use crate::{ContextInvocation, Invocation};
+
+/// Copy on write overlay over a value. Invocations are staged one after another and each of them
+/// validates against a projected view, which already includes the changes of all invocations
+/// staged before it. The target is left untouched until the shadow is committed, either with
+/// [`Shadow::flush`] or as part of a chain, since the shadow is an [`Invocation`] itself. Dropping
+/// the shadow discards the staged changes.
+///
+/// Staging runs `commit` of the staged invocation against a copy of the target, so later
+/// invocations can observe its change. Hence staged invocations must only change the context they
+/// are passed. Any other effect of their `commit` happens while staging and is not undone if the
+/// shadow is dropped. Their outputs are held back until the shadow is committed.
+///
+/// The target is cloned once, the first time a change is staged.
+pub struct Shadow<'a, T: Clone, O, E> {
+    target: &'a mut T,
+    // `None` until the first change is staged.
+    staged: Option<T>,
+    outputs: Vec<O>,
+    _error: PhantomData<fn() -> E>,
+}
+
+impl<'a, T: Clone, O, E> Shadow<'a, T, O, E> {
+    pub fn new(target: &'a mut T) -> Self {
+        Shadow {
+            target,
+            staged: None,
+            outputs: Vec::new(),
+            _error: PhantomData,
+        }
+    }
+
+    /// The target as it would look like after committing all changes staged so far.
+    pub fn view(&self) -> &T {
+        self.staged.as_ref().unwrap_or(self.target)
+    }
+
+    /// Validates `invocation` against the projected view and stages its change. If `may_fail`
+    /// fails, nothing is staged.
+    pub fn stage<I>(&mut self, invocation: I) -> Result<(), E>
+    where
+        I: ContextInvocation<T, Output = O, Error = E>,
+    {
+        let tmp = invocation.may_fail(self.view())?;
+        let staged = self.staged.get_or_insert_with(|| self.target.clone());
+        self.outputs.push(invocation.commit(tmp, staged));
+        Ok(())
+    }
+
+    /// Writes all staged changes to the target and returns the outputs of the staged invocations
+    /// in order. Can not fail.
+    pub fn flush(self) -> Vec<O> {
+        if let Some(staged) = self.staged {
+            *self.target = staged;
+        }
+        self.outputs
+    }
+}
+
+/// Every staged invocation has been validated already, so `may_fail` always succeeds. `commit`
+/// flushes the staged changes. Allows to commit the shadow only if other invocations succeed, too.
+impl<T: Clone, O, E> Invocation for Shadow<'_, T, O, E> {
+    type Error = E;
+    type Output = Vec<O>;
+    type IntermediateState = ();
+
+    fn may_fail(&self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn commit(self, _: ()) -> Vec<O> {
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{context::tests::Deposit, from_fns, Invocation};
+
+    use super::Shadow;
+
+    fn withdraw_all(balance: &mut i64, amounts: &[i64]) -> Result<Vec<i64>, &'static str> {
+        let mut shadow = Shadow::new(balance);
+        for &amount in amounts {
+            shadow.stage(Deposit(-amount))?;
+        }
+        Ok(shadow.flush())
+    }
+
+    #[test]
+    fn later_steps_observe_earlier_ones() {
+        let mut balance = 10;
+
+        let result = withdraw_all(&mut balance, &[6, 6]);
+
+        assert_eq!(Err("Insufficient funds"), result);
+        assert_eq!(10, balance);
+    }
+
+    #[test]
+    fn flush_writes_staged_changes() {
+        let mut balance = 10;
+
+        let outputs = withdraw_all(&mut balance, &[6, 3]).unwrap();
+
+        assert_eq!(vec![4, 1], outputs);
+        assert_eq!(1, balance);
+    }
+
+    #[test]
+    fn shadow_takes_part_in_chain() {
+        let mut balance = 10;
+        let mut shadow = Shadow::new(&mut balance);
+        shadow.stage(Deposit(-6)).unwrap();
+        let fail = from_fns(|| Err::<(), _>("failed"), |()| ());
+
+        let result = (shadow, fail).execute();
+
+        assert_eq!(Err("failed"), result.map(|_| ()));
+        assert_eq!(10, balance);
+    }
+}
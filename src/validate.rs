@@ -0,0 +1,256 @@
+use std::{array, fmt};
+
+use crate::{
+    failure::{abort_after_panic, abort_on_panic, trace, unwrap_prepared},
+    for_each_tuple, Failure, Invocation,
+};
+
+/// Executor for tuples, `Vec`s and arrays of invocations, which reports every failing element
+/// instead of only the first one. Useful e.g. for validating forms or configurations.
+///
+/// Only the top level elements are validated exhaustively. An element which is a composition
+/// itself, like a nested tuple or `Vec`, is prepared with its own `may_fail` and still stops at
+/// its first error. So it contributes at most one failure.
+pub trait ValidateAll: Invocation {
+    /// Executes `may_fail` of every element. If all of them succeed, all elements are committed.
+    /// Otherwise nothing is committed, the prepared elements are aborted and all errors are
+    /// returned.
+    fn validate_all(self) -> Result<Self::Output, ValidationErrors<Self::Error>>;
+}
+
+/// Error of [`ValidateAll::validate_all`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationErrors<E> {
+    /// One failure for each element whose `may_fail` failed, in order. The path of each failure
+    /// starts with the position of the element, followed by the position of the failing
+    /// invocation within the element, if it is a composition itself.
+    pub failures: Vec<Failure<E>>,
+    /// Errors raised while aborting elements which had been prepared successfully.
+    pub abort_errors: Vec<E>,
+}
+
+impl<E> ValidationErrors<E> {
+    fn new() -> Self {
+        ValidationErrors {
+            failures: Vec::new(),
+            abort_errors: Vec::new(),
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for ValidationErrors<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} invocations failed", self.failures.len())?;
+        for failure in &self.failures {
+            write!(f, "\nInvocation at {:?} failed: {failure}", failure.path)?;
+        }
+        for error in &self.abort_errors {
+            write!(f, "\nAdditionally aborting failed: {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<E> std::error::Error for ValidationErrors<E> where E: fmt::Debug + fmt::Display {}
+
+impl<I> ValidateAll for Vec<I>
+where
+    I: Invocation,
+{
+    fn validate_all(self) -> Result<Self::Output, ValidationErrors<I::Error>> {
+        let mut errors = ValidationErrors::new();
+        let mut states = Vec::with_capacity(self.len());
+        for (index, invocation) in self.iter().enumerate() {
+            let state = abort_on_panic(
+                &mut states,
+                |_| ok_or_record(invocation, index, &mut errors),
+                |states| abort_after_panic(prepared(self.iter().zip(states.drain(..)))),
+            );
+            states.push(state);
+        }
+        if errors.failures.is_empty() {
+            Ok(self.commit(states.into_iter().map(unwrap_prepared).collect()))
+        } else {
+            for (invocation, state) in self.iter().zip(states) {
+                abort_or_record(invocation, state, &mut errors);
+            }
+            Err(errors)
+        }
+    }
+}
+
+impl<I, const N: usize> ValidateAll for [I; N]
+where
+    I: Invocation,
+{
+    fn validate_all(self) -> Result<Self::Output, ValidationErrors<I::Error>> {
+        let mut errors = ValidationErrors::new();
+        let mut states: [Option<I::IntermediateState>; N] = array::from_fn(|_| None);
+        for (index, invocation) in self.iter().enumerate() {
+            states[index] = abort_on_panic(
+                &mut states,
+                |_| ok_or_record(invocation, index, &mut errors),
+                |states| {
+                    abort_after_panic(prepared(
+                        self.iter().zip(states.iter_mut().map(Option::take)),
+                    ))
+                },
+            );
+        }
+        if errors.failures.is_empty() {
+            Ok(self.commit(states.map(unwrap_prepared)))
+        } else {
+            for (invocation, state) in self.iter().zip(states) {
+                abort_or_record(invocation, state, &mut errors);
+            }
+            Err(errors)
+        }
+    }
+}
+
+macro_rules! impl_validate_all_for_tuple {
+    ($($i:tt $F:ident),+) => {
+        impl<E, $($F),+> ValidateAll for ($($F,)+)
+        where
+            $($F: Invocation<Error = E>,)+
+        {
+            fn validate_all(self) -> Result<Self::Output, ValidationErrors<E>> {
+                let mut errors = ValidationErrors::new();
+                let mut states = ($(None::<$F::IntermediateState>,)+);
+                let abort_prepared = |states: &mut ($(Option<$F::IntermediateState>,)+)| {
+                    $(
+                        if let Some(tmp) = states.$i.take() {
+                            let _ = self.$i.abort(tmp);
+                        }
+                    )+
+                };
+                $(
+                    states.$i = abort_on_panic(
+                        &mut states,
+                        |_| ok_or_record(&self.$i, $i, &mut errors),
+                        abort_prepared,
+                    );
+                )+
+                if errors.failures.is_empty() {
+                    Ok(self.commit(($(unwrap_prepared(states.$i),)+)))
+                } else {
+                    $(abort_or_record(&self.$i, states.$i, &mut errors);)+
+                    Err(errors)
+                }
+            }
+        }
+    };
+}
+
+for_each_tuple!(impl_validate_all_for_tuple);
+
+/// Executes `may_fail`. In case of failure it is recorded together with its full path.
+fn ok_or_record<I: Invocation>(
+    invocation: &I,
+    index: usize,
+    errors: &mut ValidationErrors<I::Error>,
+) -> Option<I::IntermediateState> {
+    match trace(|| invocation.may_fail_detailed()) {
+        Ok(tmp) => Some(tmp),
+        Err(mut failure) => {
+            failure.path.insert(0, index);
+            errors.failures.push(failure);
+            None
+        }
+    }
+}
+
+/// Pairs of invocations and their states, skipping the elements which have not been prepared.
+fn prepared<'a, I: Invocation + 'a>(
+    states: impl IntoIterator<Item = (&'a I, Option<I::IntermediateState>)>,
+) -> impl Iterator<Item = (&'a I, I::IntermediateState)> {
+    states
+        .into_iter()
+        .filter_map(|(invocation, state)| Some((invocation, state?)))
+}
+
+/// Aborts the state, in case the invocation had been prepared.
+fn abort_or_record<I: Invocation>(
+    invocation: &I,
+    state: Option<I::IntermediateState>,
+    errors: &mut ValidationErrors<I::Error>,
+) {
+    if let Some(Err(error)) = state.map(|tmp| invocation.abort(tmp)) {
+        errors.abort_errors.push(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::{
+        from_fns,
+        tests::{RejectOdd, Reserve},
+        Failure,
+    };
+
+    use super::{ValidateAll, ValidationErrors};
+
+    /// Failure without abort errors.
+    fn failure<E>(cause: E, path: Vec<usize>) -> Failure<E> {
+        Failure {
+            cause,
+            path,
+            abort_errors: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn report_every_error_with_position() {
+        let invocations = vec![RejectOdd(1), RejectOdd(2), RejectOdd(3)];
+
+        let result = invocations.validate_all();
+
+        let expected = ValidationErrors {
+            failures: vec![failure(1, vec![0]), failure(3, vec![2])],
+            abort_errors: Vec::new(),
+        };
+        assert_eq!(Err(expected), result)
+    }
+
+    #[test]
+    fn position_within_nested_compositions() {
+        // The nested `Vec` stops at its first error, so `5` is not reported.
+        let invocations = (RejectOdd(1), vec![RejectOdd(2), RejectOdd(3), RejectOdd(5)]);
+
+        let result = invocations.validate_all();
+
+        let expected = ValidationErrors {
+            failures: vec![failure(1, vec![0]), failure(3, vec![1, 1])],
+            abort_errors: Vec::new(),
+        };
+        assert_eq!(Err(expected), result.map(|_| ()))
+    }
+
+    #[test]
+    fn commit_all_if_every_element_succeeds() {
+        let result = [RejectOdd(2), RejectOdd(4)].validate_all();
+
+        assert_eq!(Ok([2, 4]), result)
+    }
+
+    #[test]
+    fn prepared_elements_are_aborted() {
+        let reserved = Cell::new(0);
+        let reserve = Reserve {
+            reserved: &reserved,
+            abort_error: Some("abort failed"),
+        };
+        let fail = from_fns(|| Err::<(), _>("failed"), |()| ());
+
+        let result = (reserve, fail).validate_all();
+
+        let expected = ValidationErrors {
+            failures: vec![failure("failed", vec![1])],
+            abort_errors: vec!["abort failed"],
+        };
+        assert_eq!(Err(expected), result);
+        assert_eq!(0, reserved.get());
+    }
+}